@@ -6,12 +6,11 @@ use wasmedge_anna_client::{redis_like, Client, ClientConfig};
 async fn main() -> eyre::Result<()> {
     set_up_logger()?;
 
-    let config = ClientConfig {
-        routing_ip: "127.0.0.1".parse().unwrap(),
-        routing_port_base: 12340,
-        routing_threads: 1,
-        timeout: Duration::from_secs(10),
-    };
+    let config = ClientConfig::builder()
+        .routing_ip("127.0.0.1".parse().unwrap())
+        .routing_port_base(12340)
+        .timeout(Duration::from_secs(10))
+        .build()?;
 
     // test_put_get_lww(config.clone()).await?;
     // test_transaction(config.clone()).await?;
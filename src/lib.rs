@@ -21,7 +21,7 @@
 //! the [`ClientNode::run_interactive`][nodes::ClientNode::run_interactive] method for a full
 //! list of supported commands, including background information on the used lattice types.
 
-pub use anna_api::{lattice, AnnaError, ClientKey};
+pub use anna_api::{lattice, AnnaError, ClientKey, LatticeValue};
 use eyre::anyhow;
 use messages::Tier;
 use metadata::MetadataKey;
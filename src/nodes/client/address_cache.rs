@@ -0,0 +1,148 @@
+//! Provides [`KeyAddressCache`], the cache of the KVS threads responsible for each key.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use anna_api::ClientKey;
+
+use crate::topics::KvsThread;
+
+/// Caches the KVS threads responsible for each key.
+///
+/// If a capacity is set, the least recently used key is evicted when the capacity is
+/// exceeded. If a TTL is set, a key's entry expires that long after it was resolved,
+/// so that its addresses are queried from the routing tier again.
+#[derive(Debug, Default)]
+pub(super) struct KeyAddressCache {
+    capacity: Option<usize>,
+    ttl: Option<Duration>,
+    entries: HashMap<ClientKey, Entry>,
+    /// The keys ordered by their last use.
+    recency: BTreeMap<u64, ClientKey>,
+    /// Incremented on every use of an entry.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    threads: HashSet<KvsThread>,
+    resolved: Instant,
+    last_used: u64,
+}
+
+impl KeyAddressCache {
+    pub fn new(capacity: Option<usize>, ttl: Option<Duration>) -> Self {
+        Self {
+            capacity,
+            ttl,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the KVS threads of the given key and marks the key as recently used.
+    ///
+    /// Expired entries are removed and reported as missing.
+    pub fn get(&mut self, key: &ClientKey) -> Option<&HashSet<KvsThread>> {
+        let expired = match (self.entries.get(key), self.ttl) {
+            (None, _) => return None,
+            (Some(entry), Some(ttl)) => entry.resolved.elapsed() >= ttl,
+            (Some(_), None) => false,
+        };
+        if expired {
+            self.remove(key);
+            return None;
+        }
+        self.touch(key);
+        self.entries.get(key).map(|entry| &entry.threads)
+    }
+
+    /// Adds a KVS thread to the threads of the given key.
+    ///
+    /// Evicts the least recently used key if the capacity is exceeded.
+    pub fn insert(&mut self, key: ClientKey, thread: KvsThread) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.threads.insert(thread);
+            self.touch(&key);
+            return;
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                threads: [thread].into_iter().collect(),
+                resolved: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+        if let Some(capacity) = self.capacity {
+            while self.entries.len() > capacity {
+                let lru = match self.recency.values().next() {
+                    Some(key) => key.clone(),
+                    None => break,
+                };
+                tracing::trace!("Evicting cached addresses of key {:?}", lru);
+                self.remove(&lru);
+            }
+        }
+    }
+
+    /// Removes the given key, returning its KVS threads.
+    pub fn remove(&mut self, key: &ClientKey) -> Option<HashSet<KvsThread>> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        Some(entry.threads)
+    }
+
+    /// Returns whether the given key is cached, without marking it as used.
+    #[cfg(test)]
+    pub fn contains_key(&self, key: &ClientKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the cached keys and their KVS threads in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&ClientKey, &HashSet<KvsThread>)> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (key, &entry.threads))
+    }
+
+    fn touch(&mut self, key: &ClientKey) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            self.clock += 1;
+            entry.last_used = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread(id: u32) -> KvsThread {
+        KvsThread {
+            node_id: "kvs".into(),
+            thread_id: id,
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let mut cache = KeyAddressCache::new(None, Some(Duration::from_millis(20)));
+        let key: ClientKey = "key".into();
+        cache.insert(key.clone(), thread(0));
+        assert!(cache.get(&key).is_some());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+    }
+}
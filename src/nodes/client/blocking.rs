@@ -0,0 +1,115 @@
+//! Provides [`BlockingClient`], a synchronous facade of [`Client`] for simple scripts and
+//! tools that don't use async Rust.
+
+use std::{collections::HashSet, time::Duration};
+
+use anna_api::{ClientKey, LatticeValue};
+use eyre::{bail, Context};
+use tokio::runtime::{Handle, Runtime};
+
+use super::{Client, ClientConfig, ClientError};
+
+/// A client whose methods block until the request completes.
+///
+/// Each client drives its requests on a private single-threaded runtime, so the caller
+/// doesn't need to set up tokio. Background work of the client, such as receiving
+/// responses, only progresses while a method is running.
+///
+/// A blocking client must not be created or used within an async runtime, because it
+/// would block the runtime's thread. [`BlockingClient::new`] returns an error in that
+/// case; use [`Client`] there instead.
+pub struct BlockingClient {
+    client: Client,
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    /// Creates a new blocking client.
+    ///
+    /// Fails if called from within an async runtime.
+    pub fn new(config: ClientConfig) -> eyre::Result<Self> {
+        if Handle::try_current().is_ok() {
+            bail!("a blocking client cannot be used within an async runtime, use `Client`");
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("failed to create runtime for blocking client")?;
+        Ok(Self {
+            client: Client::new(config)?,
+            runtime,
+        })
+    }
+
+    /// See [`Client::put_lww`].
+    pub fn put_lww(&mut self, key: ClientKey, value: Vec<u8>) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.put_lww(key, value))
+    }
+
+    /// See [`Client::get_lww`].
+    pub fn get_lww(&mut self, key: ClientKey) -> Result<Vec<u8>, ClientError> {
+        self.runtime.block_on(self.client.get_lww(key))
+    }
+
+    /// See [`Client::exists`].
+    pub fn exists(&mut self, key: ClientKey) -> Result<bool, ClientError> {
+        self.runtime.block_on(self.client.exists(key))
+    }
+
+    /// See [`Client::put_set`].
+    pub fn put_set(&mut self, key: ClientKey, set: HashSet<Vec<u8>>) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.put_set(key, set))
+    }
+
+    /// See [`Client::get_set`].
+    pub fn get_set(&mut self, key: ClientKey) -> Result<HashSet<Vec<u8>>, ClientError> {
+        self.runtime.block_on(self.client.get_set(key))
+    }
+
+    /// See [`Client::put_lattice`].
+    pub fn put_lattice(&mut self, key: ClientKey, value: LatticeValue) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.put_lattice(key, value))
+    }
+
+    /// See [`Client::get_lattice`].
+    pub fn get_lattice(&mut self, key: ClientKey) -> Result<LatticeValue, ClientError> {
+        self.runtime.block_on(self.client.get_lattice(key))
+    }
+
+    /// See [`Client::ping`].
+    pub fn ping(&mut self) -> eyre::Result<Duration> {
+        self.runtime.block_on(self.client.ping())
+    }
+
+    /// See [`Client::shutdown`].
+    pub fn shutdown(self) -> eyre::Result<()> {
+        self.runtime.block_on(self.client.shutdown())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    #[test]
+    fn put_then_get() {
+        let mut client = BlockingClient::new(test_config()).unwrap();
+        // the mock KVS runs on the client's runtime, while the client blocks on it
+        let kvs = client.runtime.block_on(MockKvs::start(Duration::ZERO));
+        for key in ["key", "missing"] {
+            cache_key_address(&mut client.client, &key.into(), kvs.addr);
+        }
+
+        client.put_lww("key".into(), b"value".to_vec()).unwrap();
+        assert_eq!(client.get_lww("key".into()).unwrap(), b"value");
+        assert!(!client.exists("missing".into()).unwrap());
+        client.shutdown().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_to_run_within_runtime() {
+        let err = BlockingClient::new(test_config()).err().unwrap();
+        assert!(err.to_string().contains("async runtime"), "{}", err);
+    }
+}
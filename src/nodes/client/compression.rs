@@ -0,0 +1,349 @@
+//! Provides [`Compression`] for transparently compressing large values.
+
+use anna_api::{
+    lattice::{LastWriterWinsLattice, Lattice},
+    LatticeValue,
+};
+use eyre::{bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+
+/// Values of at least this many bytes are compressed.
+const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The start of the header that tags encoded values, followed by the name of the
+/// encoding and a zero byte, e.g. `\0anna-lz4\0`.
+///
+/// Values without such a header, e.g. those written before compression was enabled,
+/// are read as is. Values that start with a header but are stored uncompressed are
+/// tagged as [`RAW_TAG`], so that they can't be mistaken for encoded values.
+const TAG_PREFIX: &[u8] = b"\0anna-";
+
+/// The maximum length of the encoding name in a header.
+const MAX_TAG_LEN: usize = 16;
+
+/// The encoding name of LZ4 compressed values.
+const LZ4_TAG: &[u8] = b"lz4";
+
+/// The encoding name of escaped uncompressed values.
+const RAW_TAG: &[u8] = b"raw";
+
+/// The maximum ratio of the decompressed to the compressed size of an LZ4 block.
+///
+/// LZ4 can't encode more than 255 bytes of output per byte of input, so larger claimed
+/// sizes come from corrupt values and are rejected before allocating the output.
+const MAX_LZ4_RATIO: usize = 255;
+
+/// The compression algorithm for large *last writer wins* values.
+///
+/// Values of at least 1 KiB that shrink when compressed are stored compressed and
+/// tagged with the algorithm. Reads decompress tagged values regardless of the
+/// configured compression, so clients with different settings can share keys.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum Compression {
+    /// [LZ4](https://lz4.github.io/lz4/), which is fast and implemented in pure Rust.
+    Lz4,
+}
+
+impl Compression {
+    /// Compresses and tags the given value if it is large enough and compressible.
+    fn compress(self, value: Vec<u8>) -> Vec<u8> {
+        if value.len() < COMPRESSION_THRESHOLD {
+            return escape(value);
+        }
+        let compressed = match self {
+            Compression::Lz4 => tagged(LZ4_TAG, &lz4_flex::compress_prepend_size(&value)),
+        };
+        if compressed.len() < value.len() {
+            compressed
+        } else {
+            escape(value)
+        }
+    }
+}
+
+/// Encodes the given value for storage, compressing it if `compression` is set.
+///
+/// Uncompressed values that start with a header are escaped, so that every client
+/// has to encode its writes, even without compression.
+pub(super) fn encode(compression: Option<Compression>, value: Vec<u8>) -> Vec<u8> {
+    match compression {
+        Some(compression) => compression.compress(value),
+        None => escape(value),
+    }
+}
+
+/// Encodes the payload of a *last writer wins* lattice, see [`encode`].
+///
+/// Other lattice types are returned unchanged.
+pub(super) fn encode_lattice(
+    compression: Option<Compression>,
+    lattice: LatticeValue,
+) -> LatticeValue {
+    match lattice {
+        LatticeValue::Lww(lattice) => {
+            let pair = lattice.into_revealed();
+            let timestamp = pair.timestamp();
+            let value = encode(compression, pair.into_value());
+            LatticeValue::Lww(LastWriterWinsLattice::from_pair(timestamp, value))
+        }
+        other => other,
+    }
+}
+
+/// Decodes the payload of a *last writer wins* lattice, see [`decompress`].
+///
+/// Other lattice types are returned unchanged.
+pub(super) fn decode_lattice(lattice: LatticeValue) -> eyre::Result<LatticeValue> {
+    Ok(match lattice {
+        LatticeValue::Lww(lattice) => {
+            let pair = lattice.into_revealed();
+            let timestamp = pair.timestamp();
+            let value = decompress(pair.into_value())?;
+            LatticeValue::Lww(LastWriterWinsLattice::from_pair(timestamp, value))
+        }
+        other => other,
+    })
+}
+
+/// Decompresses the given value if it is tagged as compressed.
+///
+/// Fails if the value has a header of an unknown encoding or if the compressed data is
+/// corrupt.
+fn decompress(mut value: Vec<u8>) -> eyre::Result<Vec<u8>> {
+    let (tag, header_len) = match split_tag(&value) {
+        Some(tag) => tag,
+        None => return Ok(value),
+    };
+    match tag {
+        RAW_TAG => Ok(value.split_off(header_len)),
+        LZ4_TAG => decompress_lz4(&value[header_len..]),
+        _ => bail!(
+            "value has an unknown encoding tag {:?}",
+            String::from_utf8_lossy(tag)
+        ),
+    }
+}
+
+/// Decompresses an LZ4 block that is prefixed with its decompressed size.
+fn decompress_lz4(compressed: &[u8]) -> eyre::Result<Vec<u8>> {
+    ensure!(
+        compressed.len() >= 4,
+        "lz4 compressed value is truncated: missing the size prefix"
+    );
+    let (size, block) = compressed.split_at(4);
+    let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+    ensure!(
+        size <= block.len().saturating_mul(MAX_LZ4_RATIO),
+        "lz4 compressed value is corrupt: {} compressed bytes can't hold {} bytes",
+        block.len(),
+        size
+    );
+    let value =
+        lz4_flex::decompress(block, size).context("failed to decompress lz4 compressed value")?;
+    ensure!(
+        value.len() == size,
+        "lz4 compressed value is truncated: expected {} bytes, got {}",
+        size,
+        value.len()
+    );
+    Ok(value)
+}
+
+/// Returns the encoding name in the header of the given value and the length of the
+/// header, or `None` if the value has no header.
+fn split_tag(value: &[u8]) -> Option<(&[u8], usize)> {
+    let rest = value.strip_prefix(TAG_PREFIX)?;
+    let len = rest.iter().take(MAX_TAG_LEN + 1).position(|&b| b == 0)?;
+    let tag = &rest[..len];
+    if tag.is_empty() || !tag.iter().all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+    Some((tag, TAG_PREFIX.len() + len + 1))
+}
+
+/// Returns the given payload behind the header of the given encoding.
+fn tagged(tag: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(TAG_PREFIX.len() + tag.len() + 1 + payload.len());
+    value.extend_from_slice(TAG_PREFIX);
+    value.extend_from_slice(tag);
+    value.push(0);
+    value.extend_from_slice(payload);
+    value
+}
+
+/// Tags the given uncompressed value as [`RAW_TAG`] if it starts with a header.
+fn escape(value: Vec<u8>) -> Vec<u8> {
+    if split_tag(&value).is_some() {
+        tagged(RAW_TAG, &value)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anna_api::{lattice::Lattice, LatticeValue};
+
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, test_config, MockKvs},
+        Client, ClientConfig, ClientError, Key,
+    };
+
+    #[tokio::test]
+    async fn large_values_are_stored_compressed() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(ClientConfig {
+            compression: Some(Compression::Lz4),
+            ..test_config()
+        })
+        .unwrap();
+        for key in ["large", "small"] {
+            cache_key_address(&mut client, &key.into(), kvs.addr);
+        }
+        let stored_len = |key: &str| match kvs.store.lock().unwrap().get(&Key::Client(key.into())) {
+            Some(LatticeValue::Lww(lattice)) => lattice.reveal().value().len(),
+            other => panic!("unexpected value {:?}", other),
+        };
+
+        let large = b"compressible ".repeat(80_000);
+        client.put_lww("large".into(), large.clone()).await.unwrap();
+        assert!(stored_len("large") < large.len() / 10);
+        assert_eq!(client.get_lww("large".into()).await.unwrap(), large);
+
+        client
+            .put_lww("small".into(), b"small".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(stored_len("small"), 5);
+
+        // values written without compression are read as is
+        kvs.put_lww("legacy", b"legacy");
+        cache_key_address(&mut client, &"legacy".into(), kvs.addr);
+        assert_eq!(client.get_lww("legacy".into()).await.unwrap(), b"legacy");
+    }
+
+    #[tokio::test]
+    async fn compressed_values_are_read_without_compression() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut writer = Client::new(ClientConfig {
+            compression: Some(Compression::Lz4),
+            ..test_config()
+        })
+        .unwrap();
+        let mut reader = Client::new(test_config()).unwrap();
+        for client in [&mut writer, &mut reader] {
+            cache_key_address(client, &"large".into(), kvs.addr);
+        }
+
+        let large = b"compressible ".repeat(80_000);
+        writer.put_lww("large".into(), large.clone()).await.unwrap();
+        assert_eq!(reader.get_lww("large".into()).await.unwrap(), large);
+    }
+
+    #[tokio::test]
+    async fn invalid_headers_are_reported() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let compressed = tagged(LZ4_TAG, &lz4_flex::compress_prepend_size(&[7; 4096]));
+        let values = [
+            (
+                "unknown",
+                tagged(b"zstd", b"payload"),
+                "unknown encoding tag",
+            ),
+            ("no-size", tagged(LZ4_TAG, b"ab"), "missing the size prefix"),
+            (
+                "huge-size",
+                tagged(LZ4_TAG, &[0xff, 0xff, 0xff, 0xff, 0x10, 0x00]),
+                "can't hold 4294967295 bytes",
+            ),
+            (
+                "truncated",
+                compressed[..compressed.len() - 4].to_vec(),
+                "failed to decompress",
+            ),
+        ];
+        for (key, value, message) in values {
+            kvs.put_lww(key, &value);
+            cache_key_address(&mut client, &key.into(), kvs.addr);
+            match client.get_lww(key.into()).await {
+                Err(ClientError::Protocol(err)) => assert!(err.contains(message), "{}", err),
+                other => panic!("unexpected result {:?} for key {}", other, key),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn values_starting_with_a_tag_round_trip() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let tagged = |payload: &[u8]| [b"\0anna-lz4\0", payload].concat();
+        // too small to compress, and incompressible
+        let small = tagged(b"raw value");
+        let large = tagged(&(0..4096u32).flat_map(u32::to_le_bytes).collect::<Vec<_>>());
+        let escaped = [b"\0anna-raw\0".as_slice(), &small].concat();
+        for compression in [None, Some(Compression::Lz4)] {
+            let mut client = Client::new(ClientConfig {
+                compression,
+                ..test_config()
+            })
+            .unwrap();
+            for (key, value) in [("small", &small), ("large", &large), ("escaped", &escaped)] {
+                cache_key_address(&mut client, &key.into(), kvs.addr);
+                client.put_lww(key.into(), value.clone()).await.unwrap();
+                assert_eq!(&client.get_lww(key.into()).await.unwrap(), value);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn lattices_are_encoded_like_lww_values() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(ClientConfig {
+            compression: Some(Compression::Lz4),
+            ..test_config()
+        })
+        .unwrap();
+        for key in ["lattice", "tx"] {
+            cache_key_address(&mut client, &key.into(), kvs.addr);
+        }
+        let stored_len = |key: &str| match kvs.store.lock().unwrap().get(&Key::Client(key.into())) {
+            Some(LatticeValue::Lww(lattice)) => lattice.reveal().value().len(),
+            other => panic!("unexpected value {:?}", other),
+        };
+        let large = b"compressible ".repeat(80_000);
+
+        client
+            .put_lww("lattice".into(), large.clone())
+            .await
+            .unwrap();
+        match client.get_lattice("lattice".into()).await.unwrap() {
+            LatticeValue::Lww(lattice) => assert_eq!(lattice.reveal().value(), &large),
+            other => panic!("unexpected value {:?}", other),
+        }
+
+        let mut tx = client.begin_transaction();
+        tx.put("tx".into(), large.clone()).await.unwrap();
+        tx.commit().await.unwrap();
+        assert!(stored_len("tx") < large.len() / 10);
+        assert_eq!(client.get_lww("tx".into()).await.unwrap(), large);
+    }
+
+    #[tokio::test]
+    async fn compressed_values_are_decompressed_as_sets() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(ClientConfig {
+            compression: Some(Compression::Lz4),
+            ..test_config()
+        })
+        .unwrap();
+        cache_key_address(&mut client, &"large".into(), kvs.addr);
+
+        let large = b"compressible ".repeat(80_000);
+        client.put_lww("large".into(), large.clone()).await.unwrap();
+        let set = client.get_set("large".into()).await.unwrap();
+        assert_eq!(set, [large].into_iter().collect());
+    }
+}
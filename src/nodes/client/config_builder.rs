@@ -0,0 +1,178 @@
+//! Provides [`ClientConfigBuilder`] for constructing a [`ClientConfig`] with defaults.
+
+use std::{net::IpAddr, time::Duration};
+
+use eyre::ContextCompat;
+
+use super::{AddressResolution, ClientConfig, Compression, ReplicaSelection, RoutingSelection};
+
+/// Builder for [`ClientConfig`], created by [`ClientConfig::builder`].
+///
+/// The routing IP and port base must be set. The other settings default to:
+///
+/// - no fallback routing nodes,
+/// - a single routing thread,
+/// - a timeout of 10 seconds,
+/// - lazy address resolution,
+/// - an unbounded address cache without expiry,
+/// - no address refresh after timeouts,
+/// - random routing thread selection,
+/// - latency-weighted replica selection,
+/// - and no compression.
+#[derive(Debug, Clone)]
+pub struct ClientConfigBuilder {
+    routing_ip: Option<IpAddr>,
+    fallback_routing_ips: Vec<IpAddr>,
+    routing_port_base: Option<u16>,
+    routing_threads: u32,
+    timeout: Duration,
+    address_resolution: AddressResolution,
+    address_cache_capacity: Option<usize>,
+    address_cache_ttl: Option<Duration>,
+    address_refresh_timeouts: Option<u32>,
+    routing_selection: RoutingSelection,
+    replica_selection: ReplicaSelection,
+    compression: Option<Compression>,
+}
+
+impl ClientConfig {
+    /// Creates a builder for a client configuration.
+    pub fn builder() -> ClientConfigBuilder {
+        ClientConfigBuilder {
+            routing_ip: None,
+            fallback_routing_ips: Vec::new(),
+            routing_port_base: None,
+            routing_threads: 1,
+            timeout: Duration::from_secs(10),
+            address_resolution: AddressResolution::Lazy,
+            address_cache_capacity: None,
+            address_cache_ttl: None,
+            address_refresh_timeouts: None,
+            routing_selection: RoutingSelection::Random,
+            replica_selection: ReplicaSelection::LatencyWeighted,
+            compression: None,
+        }
+    }
+}
+
+impl ClientConfigBuilder {
+    /// Sets the IP address of the routing node.
+    pub fn routing_ip(mut self, routing_ip: IpAddr) -> Self {
+        self.routing_ip = Some(routing_ip);
+        self
+    }
+
+    /// Adds the IP address of a routing node that is used when the others can't be
+    /// reached.
+    pub fn fallback_routing_ip(mut self, routing_ip: IpAddr) -> Self {
+        self.fallback_routing_ips.push(routing_ip);
+        self
+    }
+
+    /// Sets the TCP port base of the routing node.
+    pub fn routing_port_base(mut self, routing_port_base: u16) -> Self {
+        self.routing_port_base = Some(routing_port_base);
+        self
+    }
+
+    /// Sets the number of threads used for routing.
+    pub fn routing_threads(mut self, routing_threads: u32) -> Self {
+        self.routing_threads = routing_threads;
+        self
+    }
+
+    /// Sets the timeout for client requests.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets when key addresses are resolved.
+    pub fn address_resolution(mut self, address_resolution: AddressResolution) -> Self {
+        self.address_resolution = address_resolution;
+        self
+    }
+
+    /// Bounds the number of keys whose addresses are cached.
+    pub fn address_cache_capacity(mut self, capacity: usize) -> Self {
+        self.address_cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the time after which cached key addresses are queried again.
+    pub fn address_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.address_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the number of consecutive timed out requests for a key after which its
+    /// addresses are queried from the routing tier again.
+    pub fn address_refresh_timeouts(mut self, timeouts: u32) -> Self {
+        self.address_refresh_timeouts = Some(timeouts);
+        self
+    }
+
+    /// Sets how the routing thread for an address request is chosen.
+    pub fn routing_selection(mut self, routing_selection: RoutingSelection) -> Self {
+        self.routing_selection = routing_selection;
+        self
+    }
+
+    /// Sets how the KVS thread for a request is chosen among the replicas of its key.
+    pub fn replica_selection(mut self, replica_selection: ReplicaSelection) -> Self {
+        self.replica_selection = replica_selection;
+        self
+    }
+
+    /// Enables the compression of large values.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Validates the settings and builds the configuration.
+    pub fn build(self) -> eyre::Result<ClientConfig> {
+        let config = ClientConfig {
+            routing_ip: self.routing_ip.context("routing_ip must be set")?,
+            fallback_routing_ips: self.fallback_routing_ips,
+            routing_port_base: self
+                .routing_port_base
+                .context("routing_port_base must be set")?,
+            routing_threads: self.routing_threads,
+            timeout: self.timeout,
+            address_resolution: self.address_resolution,
+            address_cache_capacity: self.address_cache_capacity,
+            address_cache_ttl: self.address_cache_ttl,
+            address_refresh_timeouts: self.address_refresh_timeouts,
+            routing_selection: self.routing_selection,
+            replica_selection: self.replica_selection,
+            compression: self.compression,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_defaults_and_validation() {
+        let builder = ClientConfig::builder()
+            .routing_ip("127.0.0.1".parse().unwrap())
+            .routing_port_base(12340);
+        let config = builder.clone().build().unwrap();
+        assert_eq!(config.routing_threads, 1);
+        assert_eq!(config.timeout, Duration::from_secs(10));
+        assert_eq!(config.address_resolution, AddressResolution::Lazy);
+        assert_eq!(config.address_cache_capacity, None);
+        assert!(builder.clone().address_cache_capacity(0).build().is_err());
+        assert!(builder.clone().address_refresh_timeouts(0).build().is_err());
+        assert!(builder.routing_threads(0).build().is_err());
+        assert!(ClientConfig::builder()
+            .routing_port_base(12340)
+            .build()
+            .is_err());
+    }
+}
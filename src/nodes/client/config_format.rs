@@ -0,0 +1,279 @@
+//! Human-friendly (de)serialization and validation of [`ClientConfig`].
+
+use std::{fs, net::IpAddr, path::Path, time::Duration};
+
+use eyre::{bail, ensure, eyre, Context};
+use serde::{Deserialize, Deserializer, Serializer};
+
+use super::{AddressResolution, ClientConfig, Compression, ReplicaSelection, RoutingSelection};
+
+/// The unvalidated form of [`ClientConfig`] as it appears in configuration files.
+#[derive(Deserialize)]
+pub(super) struct RawClientConfig {
+    routing_ip: IpAddr,
+    #[serde(default)]
+    fallback_routing_ips: Vec<IpAddr>,
+    routing_port_base: u16,
+    routing_threads: u32,
+    #[serde(with = "duration")]
+    timeout: Duration,
+    #[serde(default)]
+    address_resolution: AddressResolution,
+    #[serde(default)]
+    address_cache_capacity: Option<usize>,
+    #[serde(default, with = "optional_duration")]
+    address_cache_ttl: Option<Duration>,
+    #[serde(default)]
+    address_refresh_timeouts: Option<u32>,
+    #[serde(default)]
+    routing_selection: RoutingSelection,
+    #[serde(default)]
+    replica_selection: ReplicaSelection,
+    #[serde(default)]
+    compression: Option<Compression>,
+}
+
+impl TryFrom<RawClientConfig> for ClientConfig {
+    type Error = eyre::Report;
+
+    fn try_from(raw: RawClientConfig) -> eyre::Result<Self> {
+        let config = Self {
+            routing_ip: raw.routing_ip,
+            fallback_routing_ips: raw.fallback_routing_ips,
+            routing_port_base: raw.routing_port_base,
+            routing_threads: raw.routing_threads,
+            timeout: raw.timeout,
+            address_resolution: raw.address_resolution,
+            address_cache_capacity: raw.address_cache_capacity,
+            address_cache_ttl: raw.address_cache_ttl,
+            address_refresh_timeouts: raw.address_refresh_timeouts,
+            routing_selection: raw.routing_selection,
+            replica_selection: raw.replica_selection,
+            compression: raw.compression,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl ClientConfig {
+    /// Reads a client configuration from the YAML file at the given path.
+    ///
+    /// The file contains the fields of [`ClientConfig`], see `example-config.yml` in the
+    /// repository root for an example.
+    pub fn from_yaml_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let file = fs::File::open(path)
+            .with_context(|| format!("failed to open config file {}", path.display()))?;
+        serde_yaml::from_reader(file)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    /// Checks that the routing thread count and port base describe valid ports, that
+    /// the address cache can hold at least one key, and that address refreshes require
+    /// at least one timeout.
+    pub(super) fn validate(&self) -> eyre::Result<()> {
+        ensure!(
+            self.routing_threads > 0,
+            "routing_threads must be at least 1"
+        );
+        let last_port = u64::from(self.routing_port_base) + u64::from(self.routing_threads) - 1;
+        ensure!(
+            last_port <= u64::from(u16::MAX),
+            "routing ports {}..={} exceed the valid port range",
+            self.routing_port_base,
+            last_port
+        );
+        ensure!(
+            self.address_cache_capacity != Some(0),
+            "address_cache_capacity must be at least 1"
+        );
+        ensure!(
+            self.address_refresh_timeouts != Some(0),
+            "address_refresh_timeouts must be at least 1"
+        );
+        Ok(())
+    }
+}
+
+/// (De)serializes durations as strings such as `"10s"` or `"500ms"`.
+///
+/// For compatibility, the default serde representation of [`Duration`] is accepted
+/// as well.
+pub(super) mod duration {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Struct(Duration),
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(*duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => parse(&text).map_err(serde::de::Error::custom),
+            Repr::Struct(duration) => Ok(duration),
+        }
+    }
+
+    /// Formats the duration with the largest unit that represents it exactly.
+    pub(in crate::nodes::client) fn format(duration: Duration) -> String {
+        if duration.subsec_nanos() == 0 {
+            format!("{}s", duration.as_secs())
+        } else if Duration::from_millis(duration.as_millis() as u64) == duration {
+            format!("{}ms", duration.as_millis())
+        } else {
+            format!("{}ns", duration.as_nanos())
+        }
+    }
+
+    /// Parses a number followed by one of the units `ns`, `ms`, `s`, `m`, or `h`.
+    pub(in crate::nodes::client) fn parse(text: &str) -> eyre::Result<Duration> {
+        let text = text.trim();
+        let split = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(|| eyre!("duration {:?} has no unit", text))?;
+        let (number, unit) = text.split_at(split);
+        let unit_nanos: u128 = match unit.trim() {
+            "ns" => 1,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            other => bail!("unknown duration unit {:?} in {:?}", other, text),
+        };
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let invalid = || eyre!("invalid duration {:?}", text);
+        let whole: u128 = whole.parse().map_err(|_| invalid())?;
+        let mut nanos = whole.checked_mul(unit_nanos).ok_or_else(invalid)?;
+        if !fraction.is_empty() {
+            let digits: u32 = fraction.len().try_into().map_err(|_| invalid())?;
+            let scale = 10u128.checked_pow(digits).ok_or_else(invalid)?;
+            let fraction: u128 = fraction.parse().map_err(|_| invalid())?;
+            nanos += fraction.checked_mul(unit_nanos).ok_or_else(invalid)? / scale;
+        }
+        let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| invalid())?;
+        Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+    }
+}
+
+/// (De)serializes optional durations like [`duration`], with `None` as null.
+pub(super) mod optional_duration {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration::format(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "duration")] Duration);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(duration)| duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_valid_config() {
+        let config: ClientConfig = serde_json::from_str(
+            r#"{
+                "routing_ip": "127.0.0.1",
+                "routing_port_base": 12340,
+                "routing_threads": 4,
+                "timeout": "500ms"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Duration::from_millis(500));
+        assert!(config.fallback_routing_ips.is_empty());
+        assert_eq!(config.address_resolution, AddressResolution::Lazy);
+        assert_eq!(config.address_cache_capacity, None);
+        assert_eq!(config.address_cache_ttl, None);
+        assert_eq!(config.address_refresh_timeouts, None);
+        assert_eq!(config.routing_selection, RoutingSelection::Random);
+        assert_eq!(config.replica_selection, ReplicaSelection::LatencyWeighted);
+        assert_eq!(config.compression, None);
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""timeout":"500ms""#), "{}", json);
+        assert_eq!(serde_json::from_str::<ClientConfig>(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn deserialize_invalid_config() {
+        let config = |threads: u32, port_base: u16, timeout: &str| {
+            serde_json::from_str::<ClientConfig>(&format!(
+                r#"{{
+                    "routing_ip": "127.0.0.1",
+                    "routing_port_base": {},
+                    "routing_threads": {},
+                    "timeout": "{}"
+                }}"#,
+                port_base, threads, timeout
+            ))
+        };
+        assert!(config(1, 12340, "10s").is_ok());
+        assert!(config(0, 12340, "10s").is_err());
+        assert!(config(2, u16::MAX, "10s").is_err());
+        assert!(config(u32::MAX, 12340, "10s").is_err());
+        assert!(config(1, 12340, "10").is_err());
+        assert!(config(1, 12340, "10 parsecs").is_err());
+    }
+
+    #[test]
+    fn duration_format_round_trip() {
+        for text in ["10s", "500ms", "1500ms", "42ns"] {
+            assert_eq!(duration::format(duration::parse(text).unwrap()), text);
+        }
+        assert_eq!(duration::parse("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(duration::parse("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(duration::parse("0.1s").unwrap(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn from_yaml_path() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/example-config.yml");
+        let config = ClientConfig::from_yaml_path(path).unwrap();
+        assert_eq!(
+            config,
+            ClientConfig::builder()
+                .routing_ip("127.0.0.1".parse().unwrap())
+                .routing_port_base(12340)
+                .build()
+                .unwrap()
+        );
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(serde_yaml::from_str::<ClientConfig>(&yaml).unwrap(), config);
+
+        let path = std::env::temp_dir().join(format!("client-config-{}.yml", std::process::id()));
+        fs::write(
+            &path,
+            "routing_ip: 127.0.0.1\nrouting_threads: 1\ntimeout: 1s\n",
+        )
+        .unwrap();
+        let err = ClientConfig::from_yaml_path(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            format!("{:#}", err).contains("routing_port_base"),
+            "{:#}",
+            err
+        );
+    }
+}
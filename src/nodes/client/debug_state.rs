@@ -0,0 +1,130 @@
+//! Provides [`ClientStateDump`], a snapshot of the internal state of a [`Client`].
+
+use std::{net::SocketAddr, sync::atomic::Ordering, time::Duration};
+
+use anna_api::ClientKey;
+use serde::Serialize;
+
+use super::Client;
+use crate::topics::KvsThread;
+
+/// A serializable snapshot of the internal state of a [`Client`].
+///
+/// Intended to be attached to bug reports. The dump contains keys and addresses, but
+/// no stored values.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStateDump {
+    /// The node ID of the client.
+    pub client_id: String,
+    /// The cached KVS threads responsible for each key.
+    pub key_addresses: Vec<(ClientKey, Vec<KvsThread>)>,
+    /// The cached TCP addresses of KVS threads.
+    pub kvs_addresses: Vec<(KvsThread, SocketAddr)>,
+    /// The open TCP connections.
+    pub connections: Vec<ConnectionDump>,
+    /// The IDs of requests that are awaiting a response.
+    pub pending_requests: Vec<String>,
+    /// The IDs of address requests that are awaiting a response.
+    pub pending_address_requests: Vec<String>,
+    /// The keys with writes held back by the write coalescing window.
+    pub pending_writes: Vec<ClientKey>,
+    /// The ID that will be assigned to the next request.
+    pub next_request_id: u64,
+}
+
+/// The state of an open TCP connection in a [`ClientStateDump`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionDump {
+    /// The address of the remote node.
+    pub addr: SocketAddr,
+    /// The KVS threads that are known to be served at this address.
+    pub kvs_threads: Vec<KvsThread>,
+    /// The time since the last message was sent on the connection.
+    pub idle: Duration,
+    /// The number of requests awaiting a response on the connection.
+    pub in_flight: usize,
+}
+
+impl Client {
+    /// Returns a snapshot of the internal state of this client, for diagnostics.
+    pub async fn debug_state(&self) -> ClientStateDump {
+        let key_addresses = self
+            .key_address_cache
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, threads)| (key.clone(), threads.iter().cloned().collect()))
+            .collect();
+        let kvs_addresses = self
+            .kvs_tcp_address_cache
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(thread, &addr)| (thread.clone(), addr))
+            .collect();
+        let connections = self
+            .connection_info()
+            .into_iter()
+            .map(|info| ConnectionDump {
+                addr: info.addr,
+                kvs_threads: info.kvs_threads,
+                idle: info.last_used.elapsed(),
+                in_flight: info.in_flight,
+            })
+            .collect();
+        let pending_requests = self
+            .response_promises
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        let pending_address_requests = self
+            .address_response_promises
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        ClientStateDump {
+            client_id: self.client_thread.node_id.clone(),
+            key_addresses,
+            kvs_addresses,
+            connections,
+            pending_requests,
+            pending_address_requests,
+            pending_writes: self.pending_writes.keys().cloned().collect(),
+            next_request_id: self.next_request_id.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn dump_reflects_caches_and_connections() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        client
+            .put_lww(key.clone(), b"value".to_vec())
+            .await
+            .unwrap();
+
+        let dump = client.debug_state().await;
+        assert_eq!(dump.key_addresses.len(), 1);
+        assert_eq!(dump.key_addresses[0].0, key);
+        assert_eq!(dump.kvs_addresses.len(), 1);
+        assert_eq!(dump.kvs_addresses[0].1, kvs.addr);
+        assert_eq!(dump.connections.len(), 1);
+        assert_eq!(dump.connections[0].addr, kvs.addr);
+        assert!(dump.pending_requests.is_empty());
+        assert!(serde_json::to_string(&dump).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+//! Provides [`ResponseDecoder`] for plugging custom value decoding into the client.
+
+use anna_api::{ClientKey, LatticeValue};
+
+use super::{Client, ClientError};
+
+/// Decodes the lattice value of a GET response into an application-specific type.
+///
+/// The generic getters such as [`Client::get_lww`] only understand the plain lattice
+/// encodings. Implement this trait to interpret values stored in formats that the
+/// client doesn't natively support, and read them through [`Client::get_with_decoder`].
+pub trait ResponseDecoder {
+    /// The decoded value type.
+    type Output;
+
+    /// Decodes the raw lattice value that was returned for the given key.
+    fn decode(&self, key: &ClientKey, lattice: LatticeValue) -> eyre::Result<Self::Output>;
+}
+
+impl Client {
+    /// Try to get the value of the given key, decoding it with the given decoder.
+    pub async fn get_with_decoder<D: ResponseDecoder>(
+        &mut self,
+        key: ClientKey,
+        decoder: &D,
+    ) -> Result<D::Output, ClientError> {
+        let lattice = self.get_lattice(key.clone()).await?;
+        Ok(decoder.decode(&key, lattice)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anna_api::lattice::Lattice;
+    use eyre::{bail, ContextCompat};
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    /// Decodes *last writer wins* values made of records with a one-byte length prefix.
+    struct LengthPrefixed;
+
+    impl ResponseDecoder for LengthPrefixed {
+        type Output = Vec<String>;
+
+        fn decode(&self, key: &ClientKey, lattice: LatticeValue) -> eyre::Result<Vec<String>> {
+            let mut bytes = match lattice {
+                LatticeValue::Lww(lattice) => lattice.into_revealed().into_value(),
+                _ => bail!("key {:?} does not hold a last writer wins value", key),
+            };
+            let mut records = Vec::new();
+            while !bytes.is_empty() {
+                let len = usize::from(bytes[0]);
+                let record = bytes.get(1..=len).context("truncated record")?;
+                records.push(String::from_utf8(record.to_vec())?);
+                bytes.drain(..=len);
+            }
+            Ok(records)
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_decoder() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "records".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        client
+            .put_lww(key.clone(), b"\x03foo\x00\x06barbaz".to_vec())
+            .await
+            .unwrap();
+        let records = client.get_with_decoder(key.clone(), &LengthPrefixed).await;
+        assert_eq!(records.unwrap(), ["foo", "", "barbaz"]);
+
+        client
+            .put_lww(key.clone(), b"\x05ab".to_vec())
+            .await
+            .unwrap();
+        let err = client.get_with_decoder(key, &LengthPrefixed).await;
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Provides [`DualWriteClient`] for replicating writes to a second cluster.
+
+use std::collections::HashSet;
+
+use anna_api::{
+    lattice::{last_writer_wins::Timestamp, LastWriterWinsLattice, SetLattice},
+    ClientKey, LatticeValue,
+};
+
+use super::{Client, ClientError};
+
+/// Client wrapper that writes to a primary and a secondary cluster.
+///
+/// Reads are served from the primary cluster only. This is intended for live migrations,
+/// where the data of the primary cluster should be continuously replicated to the
+/// secondary cluster until the cutover.
+pub struct DualWriteClient {
+    primary: Client,
+    secondary: Client,
+    tolerate_secondary_failures: bool,
+    secondary_failures: usize,
+}
+
+impl DualWriteClient {
+    /// Creates a new dual-write client.
+    ///
+    /// By default, failed writes to the secondary cluster are logged but don't fail the
+    /// operation.
+    pub fn new(primary: Client, secondary: Client) -> Self {
+        Self {
+            primary,
+            secondary,
+            tolerate_secondary_failures: true,
+            secondary_failures: 0,
+        }
+    }
+
+    /// Sets whether failed writes to the secondary cluster should fail the operation.
+    pub fn tolerate_secondary_failures(mut self, tolerate: bool) -> Self {
+        self.tolerate_secondary_failures = tolerate;
+        self
+    }
+
+    /// Returns the client of the primary cluster.
+    pub fn primary(&mut self) -> &mut Client {
+        &mut self.primary
+    }
+
+    /// Returns the client of the secondary cluster.
+    pub fn secondary(&mut self) -> &mut Client {
+        &mut self.secondary
+    }
+
+    /// Returns the number of tolerated write failures on the secondary cluster.
+    pub fn secondary_failures(&self) -> usize {
+        self.secondary_failures
+    }
+
+    /// Try to put a *last writer wins* value with the given key on both clusters.
+    ///
+    /// Both clusters receive the same timestamp.
+    pub async fn put_lww(&mut self, key: ClientKey, value: Vec<u8>) -> Result<(), ClientError> {
+        self.put_lattice(
+            key,
+            LatticeValue::Lww(LastWriterWinsLattice::from_pair(Timestamp::now(), value)),
+        )
+        .await
+    }
+
+    /// Try to get a *last writer wins* value with the given key from the primary cluster.
+    pub async fn get_lww(&mut self, key: ClientKey) -> Result<Vec<u8>, ClientError> {
+        self.primary.get_lww(key).await
+    }
+
+    /// Try to put a set value with the given key on both clusters.
+    pub async fn put_set(
+        &mut self,
+        key: ClientKey,
+        set: HashSet<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.put_lattice(key, LatticeValue::Set(SetLattice::new(set)))
+            .await
+    }
+
+    /// Try to get a set value with the given key from the primary cluster.
+    pub async fn get_set(&mut self, key: ClientKey) -> Result<HashSet<Vec<u8>>, ClientError> {
+        self.primary.get_set(key).await
+    }
+
+    async fn put_lattice(
+        &mut self,
+        key: ClientKey,
+        value: LatticeValue,
+    ) -> Result<(), ClientError> {
+        self.primary.put_lattice(key.clone(), value.clone()).await?;
+        if let Err(err) = self.secondary.put_lattice(key.clone(), value).await {
+            if !self.tolerate_secondary_failures {
+                return Err(eyre::Report::new(err)
+                    .wrap_err("failed to write to secondary cluster")
+                    .into());
+            }
+            self.secondary_failures += 1;
+            tracing::warn!(
+                "Failed to write key {:?} to secondary cluster: {:?}",
+                key,
+                err
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, dead_addr, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn put_lands_in_both_clusters() {
+        let primary_kvs = MockKvs::start(Duration::ZERO).await;
+        let secondary_kvs = MockKvs::start(Duration::ZERO).await;
+        let key: ClientKey = "key".into();
+        let mut primary = Client::new(test_config()).unwrap();
+        cache_key_address(&mut primary, &key, primary_kvs.addr);
+        let mut secondary = Client::new(test_config()).unwrap();
+        cache_key_address(&mut secondary, &key, secondary_kvs.addr);
+
+        let mut client = DualWriteClient::new(primary, secondary);
+        client
+            .put_lww(key.clone(), b"value".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(client.get_lww(key.clone()).await.unwrap(), b"value");
+        assert_eq!(client.secondary().get_lww(key).await.unwrap(), b"value");
+    }
+
+    #[tokio::test]
+    async fn secondary_failure_is_tolerated() {
+        let primary_kvs = MockKvs::start(Duration::ZERO).await;
+        let key: ClientKey = "key".into();
+        let mut primary = Client::new(test_config()).unwrap();
+        cache_key_address(&mut primary, &key, primary_kvs.addr);
+        let mut secondary = Client::new(test_config()).unwrap();
+        cache_key_address(&mut secondary, &key, dead_addr().await);
+
+        let mut client = DualWriteClient::new(primary, secondary);
+        client
+            .put_lww(key.clone(), b"value".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(client.secondary_failures(), 1);
+
+        let mut client = client.tolerate_secondary_failures(false);
+        assert!(client.put_lww(key, b"value".to_vec()).await.is_err());
+    }
+}
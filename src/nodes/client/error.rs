@@ -0,0 +1,161 @@
+//! Provides [`ClientError`], a classification of client errors for `match`-based handling.
+
+use std::{error::Error, fmt, io};
+
+use anna_api::AnnaError;
+
+/// The kind of failure behind an error returned by the client.
+///
+/// The key-value methods of [`Client`](crate::Client), such as
+/// [`get_lww`](crate::Client::get_lww), return a `ClientError`, so the kind of failure
+/// can be matched on instead of inspecting the message:
+///
+/// ```no_run
+/// # async fn example(client: &mut wasmedge_anna_client::Client) {
+/// use wasmedge_anna_client::ClientError;
+///
+/// match client.get_lww("key".into()).await {
+///     Ok(value) => println!("{:?}", value),
+///     Err(ClientError::KeyDoesNotExist) => println!("not found"),
+///     Err(err) => println!("failed: {}", err),
+/// }
+/// # }
+/// ```
+///
+/// Other APIs, such as transactions and the [`redis_like`](crate::redis_like) layer,
+/// return [`eyre::Report`]s that carry more context. They can be converted into a
+/// `ClientError` as well.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The requested key does not exist.
+    KeyDoesNotExist,
+    /// No response arrived within the configured timeout.
+    ///
+    /// The message names the timed out request, with its key and target address.
+    Timeout(String),
+    /// No KVS node is responsible for the key.
+    NoReplicas,
+    /// Sending or receiving a message failed.
+    Transport(io::Error),
+    /// The cluster responded unexpectedly, or the request was invalid.
+    Protocol(String),
+    /// The stored value has an unexpected lattice type.
+    Lattice(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::KeyDoesNotExist => write!(f, "key does not exist"),
+            ClientError::Timeout(message) => write!(f, "timed out: {}", message),
+            ClientError::NoReplicas => write!(f, "no replicas for key"),
+            ClientError::Transport(err) => write!(f, "transport error: {}", err),
+            ClientError::Protocol(message) => write!(f, "protocol error: {}", message),
+            ClientError::Lattice(message) => write!(f, "lattice error: {}", message),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Transport(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AnnaError> for ClientError {
+    fn from(err: AnnaError) -> Self {
+        match err {
+            AnnaError::KeyDoesNotExist => ClientError::KeyDoesNotExist,
+            AnnaError::Timeout => ClientError::Timeout(err.to_string()),
+            AnnaError::NoServers => ClientError::NoReplicas,
+            AnnaError::Lattice => ClientError::Lattice(err.to_string()),
+            _ => ClientError::Protocol(err.to_string()),
+        }
+    }
+}
+
+impl From<eyre::Report> for ClientError {
+    /// Classifies the report, see the conversion from `&eyre::Report`.
+    fn from(report: eyre::Report) -> Self {
+        Self::from(&report)
+    }
+}
+
+impl From<&eyre::Report> for ClientError {
+    /// Classifies the report by the first recognized error in its chain.
+    ///
+    /// A [`ClientError::Timeout`] in the chain is returned with its own message, which
+    /// names the request. Reports without a recognized cause become
+    /// [`ClientError::Protocol`] with the full message.
+    fn from(report: &eyre::Report) -> Self {
+        for cause in report.chain() {
+            if let Some(err) = cause.downcast_ref::<ClientError>() {
+                match err {
+                    ClientError::KeyDoesNotExist => return ClientError::KeyDoesNotExist,
+                    ClientError::Timeout(message) => return ClientError::Timeout(message.clone()),
+                    ClientError::NoReplicas => return ClientError::NoReplicas,
+                    ClientError::Transport(_)
+                    | ClientError::Protocol(_)
+                    | ClientError::Lattice(_) => {}
+                }
+            }
+            if let Some(err) = cause.downcast_ref::<AnnaError>() {
+                return match err {
+                    AnnaError::KeyDoesNotExist => ClientError::KeyDoesNotExist,
+                    AnnaError::Timeout => ClientError::Timeout(format!("{:#}", report)),
+                    AnnaError::NoServers => ClientError::NoReplicas,
+                    AnnaError::Lattice => ClientError::Lattice(format!("{:#}", report)),
+                    _ => ClientError::Protocol(format!("{:#}", report)),
+                };
+            }
+            if let Some(err) = cause.downcast_ref::<io::Error>() {
+                let message = format!("{:#}", report);
+                return ClientError::Transport(io::Error::new(err.kind(), message));
+            }
+        }
+        ClientError::Protocol(format!("{:#}", report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::WrapErr;
+
+    use super::*;
+
+    #[test]
+    fn classifies_error_chain() {
+        let report = eyre::Report::new(AnnaError::KeyDoesNotExist).wrap_err("get failed");
+        assert!(matches!(
+            ClientError::from(report),
+            ClientError::KeyDoesNotExist
+        ));
+
+        let report = eyre::Report::new(ClientError::Timeout("request 1 to kvs".into()))
+            .wrap_err("get failed");
+        assert!(matches!(
+            ClientError::from(report),
+            ClientError::Timeout(message) if message == "request 1 to kvs"
+        ));
+
+        let io_error = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        let report = Err::<(), _>(io_error)
+            .wrap_err("failed to connect")
+            .unwrap_err();
+        match ClientError::from(report) {
+            ClientError::Transport(err) => {
+                assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let report = eyre::eyre!("response has no tuples");
+        assert!(matches!(
+            ClientError::from(report),
+            ClientError::Protocol(message) if message.contains("no tuples")
+        ));
+    }
+}
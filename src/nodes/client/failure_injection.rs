@@ -0,0 +1,185 @@
+//! Provides [`FailureInjection`] for testing the client under adverse network conditions.
+//!
+//! This module is only available with the `failure-injection` feature.
+
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
+
+use anna_api::ClientKey;
+use eyre::bail;
+use rand::Rng;
+use tokio::{io::AsyncWriteExt, net::tcp};
+
+use super::Client;
+use crate::{messages::TcpMessage, Key};
+
+/// Describes which failures the transport of a [`Client`] should inject.
+///
+/// Failures are applied to outgoing TCP messages, before they are written to the
+/// connection. The default value injects no failures.
+#[derive(Debug, Clone, Default)]
+pub struct FailureInjection {
+    /// The fraction of messages that are silently dropped, between `0.0` and `1.0`.
+    pub drop_rate: f64,
+    /// The fraction of messages that are replaced by an undecodable payload, between
+    /// `0.0` and `1.0`.
+    pub corrupt_rate: f64,
+    /// The latency added before each message is sent.
+    pub latency: Duration,
+    /// Messages concerning one of these keys fail with an error.
+    pub failing_keys: HashSet<ClientKey>,
+    /// Messages to one of these addresses fail with an error.
+    pub failing_addrs: HashSet<SocketAddr>,
+}
+
+/// What to do with an outgoing message.
+pub(super) enum Injected {
+    Send,
+    Drop,
+    Corrupt,
+}
+
+impl FailureInjection {
+    /// Applies the configured latency and decides the fate of the given message.
+    pub(super) async fn inject(
+        &self,
+        addr: SocketAddr,
+        message: &TcpMessage,
+    ) -> eyre::Result<Injected> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        if self.failing_addrs.contains(&addr) {
+            bail!("injected failure for address {}", addr);
+        }
+        if let Some(key) = message_keys(message).find(|k| self.failing_keys.contains(k)) {
+            bail!("injected failure for key {:?}", key);
+        }
+        let mut rng = rand::thread_rng();
+        Ok(if rng.gen_bool(self.drop_rate) {
+            Injected::Drop
+        } else if rng.gen_bool(self.corrupt_rate) {
+            Injected::Corrupt
+        } else {
+            Injected::Send
+        })
+    }
+}
+
+/// Sends a correctly framed message whose payload can't be deserialized.
+pub(super) async fn send_corrupted(stream_tx: &mut tcp::OwnedWriteHalf) -> eyre::Result<()> {
+    const PAYLOAD: &[u8] = b"\xffcorrupted";
+
+    stream_tx
+        .write_all(&(PAYLOAD.len() as u64).to_le_bytes())
+        .await?;
+    stream_tx.write_all(PAYLOAD).await?;
+    Ok(())
+}
+
+/// Returns the client keys that the given message concerns.
+fn message_keys(message: &TcpMessage) -> Box<dyn Iterator<Item = &ClientKey> + '_> {
+    use crate::messages::request::RequestData;
+
+    fn client_key(key: &Key) -> Option<&ClientKey> {
+        match key {
+            Key::Client(key) => Some(key),
+            Key::Metadata(_) => None,
+        }
+    }
+
+    match message {
+        TcpMessage::AddressRequest(request) => Box::new(request.keys.iter()),
+        TcpMessage::Request(request) => match &request.request {
+            RequestData::Get { keys } => Box::new(keys.iter().filter_map(client_key)),
+            RequestData::Put { tuples } => {
+                Box::new(tuples.iter().filter_map(|t| client_key(&t.key)))
+            }
+        },
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+impl Client {
+    /// Sets the failures that should be injected into the transport of this client.
+    pub fn set_failure_injection(&mut self, injection: FailureInjection) {
+        self.failure_injection = injection;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn dropped_request_times_out() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        client.set_failure_injection(FailureInjection {
+            drop_rate: 1.0,
+            ..Default::default()
+        });
+
+        let result = tokio::time::timeout(Duration::from_millis(100), client.get_lww(key)).await;
+        assert!(result.is_err());
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn targeted_failures_and_latency() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("healthy", b"value");
+        let mut client = Client::new(test_config()).unwrap();
+        let healthy: ClientKey = "healthy".into();
+        let failing: ClientKey = "failing".into();
+        cache_key_address(&mut client, &healthy, kvs.addr);
+        cache_key_address(&mut client, &failing, kvs.addr);
+        client.set_failure_injection(FailureInjection {
+            latency: Duration::from_millis(50),
+            failing_keys: [failing.clone()].into_iter().collect(),
+            ..Default::default()
+        });
+
+        let err = client.get_lww(failing).await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("injected failure"),
+            "{:#}",
+            err
+        );
+
+        let start = Instant::now();
+        assert_eq!(client.get_lww(healthy).await.unwrap(), b"value");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn corrupted_message_closes_connection() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let disconnects_clone = disconnects.clone();
+        client.on_disconnect(move |_, _| {
+            disconnects_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        client.set_failure_injection(FailureInjection {
+            corrupt_rate: 1.0,
+            ..Default::default()
+        });
+
+        let result = tokio::time::timeout(Duration::from_millis(500), client.get_lww(key)).await;
+        assert!(!matches!(result, Ok(Ok(_))));
+        assert_eq!(disconnects.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! Provides [`ClientMetrics`], counters and latencies of the requests of a [`Client`].
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use super::{Client, ClientError};
+
+/// The upper bounds of the buckets of [`LatencyHistogram`].
+const LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/// A snapshot of the metrics of a [`Client`], created by [`Client::metrics_snapshot`].
+///
+/// All handles of a client share the same metrics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ClientMetrics {
+    /// The number of requests sent to KVS threads.
+    pub requests: u64,
+    /// The number of failed requests and of errors reported for keys, by kind.
+    pub errors: ErrorCounts,
+    /// The number of key address lookups that were served from the cache.
+    pub address_cache_hits: u64,
+    /// The number of key address lookups that required a query to the routing tier.
+    pub address_cache_misses: u64,
+    /// The time from the creation of requests to the receipt of their responses.
+    pub latency: LatencyHistogram,
+    /// The number of responses that arrived without a waiting request.
+    ///
+    /// These are late responses to requests that timed out or were cancelled, such as
+    /// the slower read of [`Client::get_lww_hedged`], and responses with an unknown
+    /// request ID.
+    pub orphan_responses: u64,
+}
+
+/// The number of errors of each [`ClientError`] kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ErrorCounts {
+    /// See [`ClientError::KeyDoesNotExist`].
+    pub key_does_not_exist: u64,
+    /// See [`ClientError::Timeout`].
+    pub timeout: u64,
+    /// See [`ClientError::NoReplicas`].
+    pub no_replicas: u64,
+    /// See [`ClientError::Transport`].
+    pub transport: u64,
+    /// See [`ClientError::Protocol`].
+    pub protocol: u64,
+    /// See [`ClientError::Lattice`].
+    pub lattice: u64,
+}
+
+/// A histogram of request latencies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LatencyHistogram {
+    /// The inclusive upper bound of each bucket and the number of latencies in it.
+    ///
+    /// Latencies above the last bound are only counted in `count`.
+    pub buckets: Vec<(Duration, u64)>,
+    /// The number of recorded latencies.
+    pub count: u64,
+    /// The sum of all recorded latencies.
+    pub sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: LATENCY_BUCKETS.iter().map(|&bound| (bound, 0)).collect(),
+            count: 0,
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+/// The live metrics of a client, updated atomically.
+#[derive(Debug, Default)]
+pub(super) struct Metrics {
+    requests: AtomicU64,
+    errors: [AtomicU64; 6],
+    address_cache_hits: AtomicU64,
+    address_cache_misses: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_count: AtomicU64,
+    latency_sum_nanos: AtomicU64,
+    orphan_responses: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, error: &ClientError) {
+        let index = match error {
+            ClientError::KeyDoesNotExist => 0,
+            ClientError::Timeout(_) => 1,
+            ClientError::NoReplicas => 2,
+            ClientError::Transport(_) => 3,
+            ClientError::Protocol(_) => 4,
+            ClientError::Lattice(_) => 5,
+        };
+        self.errors[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_address_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.address_cache_hits
+        } else {
+            &self.address_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&bound| latency <= bound) {
+            self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.latency_sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub fn record_orphan_response(&self) {
+        self.orphan_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ClientMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ClientMetrics {
+            requests: load(&self.requests),
+            errors: ErrorCounts {
+                key_does_not_exist: load(&self.errors[0]),
+                timeout: load(&self.errors[1]),
+                no_replicas: load(&self.errors[2]),
+                transport: load(&self.errors[3]),
+                protocol: load(&self.errors[4]),
+                lattice: load(&self.errors[5]),
+            },
+            address_cache_hits: load(&self.address_cache_hits),
+            address_cache_misses: load(&self.address_cache_misses),
+            latency: LatencyHistogram {
+                buckets: LATENCY_BUCKETS
+                    .iter()
+                    .zip(&self.latency_buckets)
+                    .map(|(&bound, count)| (bound, load(count)))
+                    .collect(),
+                count: load(&self.latency_count),
+                sum: Duration::from_nanos(load(&self.latency_sum_nanos)),
+            },
+            orphan_responses: load(&self.orphan_responses),
+        }
+    }
+}
+
+impl Client {
+    /// Returns the current request metrics of this client.
+    pub fn metrics_snapshot(&self) -> ClientMetrics {
+        self.metrics.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nodes::client::tests::{mock_routing, test_config, MockKvs},
+        topics::KvsThread,
+        ClientConfig,
+    };
+
+    #[tokio::test]
+    async fn cold_then_warm_get() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+
+        client.get_lww("key".into()).await.unwrap();
+        let metrics = client.metrics_snapshot();
+        assert_eq!(metrics.address_cache_misses, 1);
+        assert_eq!(metrics.address_cache_hits, 0);
+
+        client.get_lww("key".into()).await.unwrap();
+        assert!(client.get_lww("missing".into()).await.is_err());
+        let metrics = client.metrics_snapshot();
+        assert_eq!(metrics.address_cache_misses, 2);
+        assert_eq!(metrics.address_cache_hits, 1);
+        assert_eq!(metrics.requests, 3);
+        assert_eq!(metrics.latency.count, 3);
+        assert_eq!(
+            metrics.errors,
+            ErrorCounts {
+                key_does_not_exist: 1,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,9 +1,14 @@
 //! New generation of client node that expose a GET/PUT-based interface to users.
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    io,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
@@ -13,61 +18,273 @@ use anna_api::{
         last_writer_wins::Timestamp,
         LastWriterWinsLattice, Lattice, MapLattice, MaxLattice, SetLattice,
     },
-    ClientKey, LatticeValue,
+    AnnaError, ClientKey, LatticeValue,
+};
+use eyre::{bail, eyre, Context, ContextCompat};
+use futures::{
+    future::{self, Either},
+    stream::FuturesUnordered,
+    Future, StreamExt,
 };
-use eyre::{eyre, Context, ContextCompat};
-use futures::Future;
-use rand::prelude::IteratorRandom;
+use rand::prelude::{IteratorRandom, SliceRandom};
 use serde::{Deserialize, Serialize};
 use tokio::{
+    io::AsyncWriteExt,
     net::{tcp, TcpStream},
-    sync::{oneshot, Mutex},
+    sync::{oneshot, Mutex, OnceCell},
+    task::JoinHandle,
 };
+use tracing::Instrument;
 
 use crate::{
     messages::{AddressRequest, AddressResponse, Response, TcpMessage},
-    nodes::{receive_tcp_message, send_tcp_message},
+    nodes::{receive_tcp_message, send_tcp_message_buffered, BufferPool},
     topics::{ClientThread, KvsThread, RoutingThread},
+    Key,
 };
 
-use self::{client_request::ClientRequest, transaction::ReadCommittedTransaction};
+#[cfg(any(test, feature = "failure-injection"))]
+pub use self::failure_injection::FailureInjection;
+use self::{
+    address_cache::KeyAddressCache, client_request::ClientRequest, metrics::Metrics,
+    transaction::ReadCommittedTransaction,
+};
+pub use self::{
+    compression::Compression,
+    config_builder::ClientConfigBuilder,
+    debug_state::{ClientStateDump, ConnectionDump},
+    decoder::ResponseDecoder,
+    dual_write::DualWriteClient,
+    error::ClientError,
+    metrics::{ClientMetrics, ErrorCounts, LatencyHistogram},
+    namespace::NamespacedClient,
+    options::{RequestOptions, WithOptions},
+    retry::{ReconnectBackoff, RetryPolicy},
+    typed_key::TypedKey,
+};
 
+mod address_cache;
+pub mod blocking;
 mod client_request;
+mod compression;
+mod config_builder;
+mod config_format;
+mod debug_state;
+mod decoder;
+mod dual_write;
+mod error;
+#[cfg(any(test, feature = "failure-injection"))]
+mod failure_injection;
+mod metrics;
+mod namespace;
+mod options;
+#[cfg(feature = "opentelemetry")]
+mod otel;
 pub mod redis_like;
+mod retry;
 mod transaction;
+mod typed_key;
 
 /// Configuration for [`Client`].
+///
+/// Create a configuration with [`ClientConfig::builder`] or deserialize it, e.g. with
+/// [`ClientConfig::from_yaml_path`]. The struct is non-exhaustive, so that new settings
+/// can be added without breaking existing code.
+///
+/// When deserialized, the `timeout` is given as a string such as `"10s"` or `"500ms"`,
+/// and the routing thread count and port base are validated.
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
+#[serde(try_from = "config_format::RawClientConfig")]
+#[non_exhaustive]
 pub struct ClientConfig {
     /// IP address of routing node.
     pub routing_ip: IpAddr,
+    /// IP addresses of further routing nodes, which are used when the routing node at
+    /// [`routing_ip`][Self::routing_ip] can't be reached.
+    ///
+    /// All routing nodes use the same port base and thread count.
+    #[serde(default)]
+    pub fallback_routing_ips: Vec<IpAddr>,
     /// TCP port base of routing node.
     pub routing_port_base: u16,
     /// Number of threads used for routing.
     pub routing_threads: u32,
     /// Timeout for client requests.
+    #[serde(with = "config_format::duration")]
     pub timeout: Duration,
+    /// When key addresses are resolved.
+    #[serde(default)]
+    pub address_resolution: AddressResolution,
+    /// The maximum number of keys whose addresses are cached.
+    ///
+    /// When exceeded, the addresses of the least recently used key are evicted.
+    /// Unbounded if `None`.
+    #[serde(default)]
+    pub address_cache_capacity: Option<usize>,
+    /// The time after which cached key addresses are queried from the routing tier
+    /// again, even if they are still in use. Never if `None`.
+    #[serde(default, with = "config_format::optional_duration")]
+    pub address_cache_ttl: Option<Duration>,
+    /// The number of consecutive timed out requests for a key after which its cached
+    /// addresses are invalidated, so that they are queried from the routing tier again.
+    ///
+    /// This recovers from KVS threads that stopped responding without the routing tier
+    /// telling the client. Never if `None`.
+    #[serde(default)]
+    pub address_refresh_timeouts: Option<u32>,
+    /// How the routing thread for an address request is chosen.
+    #[serde(default)]
+    pub routing_selection: RoutingSelection,
+    /// How the KVS thread for a request is chosen among the replicas of its key.
+    #[serde(default)]
+    pub replica_selection: ReplicaSelection,
+    /// The compression of large *last writer wins* values. Disabled if `None`.
+    #[serde(default)]
+    pub compression: Option<Compression>,
+}
+
+/// Specifies when a [`Client`] resolves the addresses of keys.
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
+pub enum AddressResolution {
+    /// Resolve the address of a key on its first access.
+    #[default]
+    Lazy,
+    /// Resolve the addresses of the given keys and connect to the KVS threads serving
+    /// them when the client is created through [`Client::connect`].
+    ///
+    /// Other keys are still resolved on their first access.
+    Eager(Vec<ClientKey>),
+}
+
+/// Specifies how a [`Client`] chooses the routing thread for an address request.
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum RoutingSelection {
+    /// Choose a random routing thread for every request.
+    #[default]
+    Random,
+    /// Cycle through the routing threads in order.
+    RoundRobin,
+    /// Choose the routing thread by the hash of the (first) requested key, so that
+    /// requests for the same key always go to the same routing thread.
+    HashByKey,
+}
+
+/// Specifies how a [`Client`] chooses the KVS thread for a request among the replicas
+/// of its key.
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum ReplicaSelection {
+    /// Choose a random replica, favoring replicas with lower response latencies, see
+    /// [`Client::kvs_latency_estimates`].
+    #[default]
+    LatencyWeighted,
+    /// Choose the replica by the hash of the key and the replicas, so that requests for
+    /// the same key always go to the same replica while the replica set is unchanged.
+    ///
+    /// This uses rendezvous hashing: when a replica is added or removed, only the keys
+    /// that move to or from it change replicas. Pinning the reads of a key to one KVS
+    /// thread improves the hit rate of the caches on the KVS side.
+    HashByKey,
+}
+
+/// Specifies how many replicas of a key must answer a read, see
+/// [`Client::get_lww_many_with_consistency`].
+///
+/// The levels are ordered from weakest to strongest.
+#[derive(
+    Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize,
+)]
+pub enum ConsistencyLevel {
+    /// Read from a single replica, which might not have received the latest writes yet.
+    #[default]
+    Eventual,
+    /// Read from a majority of the known replicas and return the newest of their values.
+    Quorum,
 }
 
 /// Anna client.
 pub struct Client {
+    config: ClientConfig,
     client_thread: ClientThread,
-    routing_ip: IpAddr,
-    routing_port_base: u16,
     routing_threads: Vec<RoutingThread>,
-    _timeout: Duration,
-    next_request_id: u32,
-    key_address_cache: HashMap<ClientKey, HashSet<KvsThread>>,
-    kvs_tcp_address_cache: HashMap<KvsThread, SocketAddr>,
-    tcp_write_halves: HashMap<SocketAddr, Arc<Mutex<tcp::OwnedWriteHalf>>>,
+    next_routing_thread: Arc<AtomicUsize>,
+    next_request_id: Arc<AtomicU64>,
+    key_address_cache: Arc<std::sync::Mutex<KeyAddressCache>>,
+    kvs_tcp_address_cache: Arc<std::sync::Mutex<HashMap<KvsThread, SocketAddr>>>,
+    kvs_latency: Arc<std::sync::Mutex<HashMap<KvsThread, Duration>>>,
+    /// The routing nodes that could not be connected to, and when.
+    failed_routers: Arc<std::sync::Mutex<HashMap<IpAddr, Instant>>>,
+    /// The number of consecutive timed out requests of keys whose last request timed out.
+    key_timeouts: Arc<std::sync::Mutex<HashMap<ClientKey, u32>>>,
+    /// The latest known vector clock of every key accessed through the causal methods.
+    causal_clocks: Arc<std::sync::Mutex<HashMap<ClientKey, VectorClock>>>,
+    connections: ConnectionPool,
     address_response_promises:
         Arc<Mutex<HashMap<String /* request_id */, oneshot::Sender<AddressResponse>>>>,
     response_promises: Arc<Mutex<HashMap<String /* request_id */, oneshot::Sender<Response>>>>,
+    connection_hooks: Arc<std::sync::Mutex<ConnectionHooks>>,
+    buffer_pool: BufferPool,
+    key_validator: Option<KeyValidator>,
+    write_coalescing_window: Duration,
+    pending_writes: HashMap<ClientKey, PendingWrite>,
+    retry_policy: RetryPolicy,
+    metrics: Arc<Metrics>,
+    #[cfg(any(test, feature = "failure-injection"))]
+    failure_injection: FailureInjection,
+}
+
+/// Creates another handle to the same client.
+///
+/// Handles share their TCP connections and reconnect backoff, address caches, latency
+/// estimates, routing node failures, timeout counts, causal vector clocks, connection
+/// hooks, and request ID counter, so they can be used concurrently from different tasks.
+/// The key validator, write coalescing window, and failure injection settings are copied
+/// and can be changed per handle. Writes held back by the write coalescing window are
+/// not shared.
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            client_thread: self.client_thread.clone(),
+            routing_threads: self.routing_threads.clone(),
+            next_routing_thread: self.next_routing_thread.clone(),
+            next_request_id: self.next_request_id.clone(),
+            key_address_cache: self.key_address_cache.clone(),
+            kvs_tcp_address_cache: self.kvs_tcp_address_cache.clone(),
+            kvs_latency: self.kvs_latency.clone(),
+            failed_routers: self.failed_routers.clone(),
+            key_timeouts: self.key_timeouts.clone(),
+            causal_clocks: self.causal_clocks.clone(),
+            connections: self.connections.clone(),
+            address_response_promises: self.address_response_promises.clone(),
+            response_promises: self.response_promises.clone(),
+            connection_hooks: self.connection_hooks.clone(),
+            buffer_pool: self.buffer_pool.clone(),
+            key_validator: self.key_validator.clone(),
+            write_coalescing_window: self.write_coalescing_window,
+            pending_writes: HashMap::new(),
+            retry_policy: self.retry_policy.clone(),
+            metrics: self.metrics.clone(),
+            #[cfg(any(test, feature = "failure-injection"))]
+            failure_injection: self.failure_injection.clone(),
+        }
+    }
+}
+
+/// A write that is held back by the write coalescing window.
+struct PendingWrite {
+    value: LatticeValue,
+    since: Instant,
 }
 
+type KeyValidator = Arc<dyn Fn(&ClientKey) -> eyre::Result<()> + Send + Sync>;
+
+#[derive(Clone)]
 struct ThisClient {
     address_response_promises: Arc<Mutex<HashMap<String, oneshot::Sender<AddressResponse>>>>,
     response_promises: Arc<Mutex<HashMap<String, oneshot::Sender<Response>>>>,
+    connection_hooks: Arc<std::sync::Mutex<ConnectionHooks>>,
+    connections: ConnectionPool,
+    metrics: Arc<Metrics>,
 }
 
 impl ThisClient {
@@ -75,6 +292,270 @@ impl ThisClient {
         Self {
             address_response_promises: client.address_response_promises.clone(),
             response_promises: client.response_promises.clone(),
+            connection_hooks: client.connection_hooks.clone(),
+            connections: client.connections.clone(),
+            metrics: client.metrics.clone(),
+        }
+    }
+}
+
+/// Diagnostic information about an open TCP connection of a [`Client`].
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// The address of the remote node.
+    pub addr: SocketAddr,
+    /// The KVS threads that are known to be served at this address.
+    ///
+    /// This is empty for connections to routing threads.
+    pub kvs_threads: Vec<KvsThread>,
+    /// When the last message was sent on the connection.
+    pub last_used: Instant,
+    /// The number of requests sent on the connection that are still awaiting a response.
+    pub in_flight: usize,
+}
+
+/// Usage statistics of a TCP connection, shared with its receive loop.
+#[derive(Debug)]
+struct ConnectionStats {
+    last_used: std::sync::Mutex<Instant>,
+    in_flight: AtomicUsize,
+}
+
+impl ConnectionStats {
+    fn new() -> Self {
+        Self {
+            last_used: std::sync::Mutex::new(Instant::now()),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn sent(&self, message: &TcpMessage) {
+        *self.last_used.lock().unwrap() = Instant::now();
+        if matches!(
+            message,
+            TcpMessage::Request(_) | TcpMessage::AddressRequest(_)
+        ) {
+            self.in_flight.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn received_response(&self) {
+        let _ = self
+            .in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+}
+
+/// An open TCP connection to a routing or KVS thread.
+#[derive(Clone)]
+struct Connection {
+    writer: Arc<Mutex<tcp::OwnedWriteHalf>>,
+    stats: Arc<ConnectionStats>,
+    receiver: Arc<JoinHandle<()>>,
+}
+
+/// The TCP connections of a [`Client`], keyed by remote address.
+///
+/// Connection establishment is coalesced: concurrent first uses of the same address
+/// share a single connection attempt, so at most one connection per address is opened.
+/// Addresses that could not be connected to are backed off, see [`ReconnectBackoff`].
+#[derive(Clone, Default)]
+struct ConnectionPool {
+    connections: Arc<std::sync::Mutex<HashMap<SocketAddr, Arc<OnceCell<Connection>>>>>,
+    /// Set by [`Client::shutdown`]; no new connections are opened afterwards.
+    closed: Arc<AtomicBool>,
+    backoff: Arc<std::sync::Mutex<ReconnectBackoff>>,
+    /// The addresses whose last connection attempts failed.
+    failures: Arc<std::sync::Mutex<HashMap<SocketAddr, ConnectFailure>>>,
+}
+
+impl ConnectionPool {
+    /// Returns the connection to the given address, opening it if necessary.
+    ///
+    /// If opening the connection fails, the next call tries again.
+    async fn get(&self, this: ThisClient, addr: SocketAddr) -> eyre::Result<Connection> {
+        let cell = {
+            let mut connections = self.connections.lock().unwrap();
+            if self.is_closed() {
+                bail!("client is shut down");
+            }
+            connections.entry(addr).or_default().clone()
+        };
+        cell.get_or_try_init(|| self.connect(this, addr))
+            .await
+            .cloned()
+    }
+
+    /// Opens a connection to the given address, unless the address is backed off after
+    /// failed connection attempts.
+    async fn connect(&self, this: ThisClient, addr: SocketAddr) -> eyre::Result<Connection> {
+        if let Some(failure) = self.failures.lock().unwrap().get(&addr) {
+            let remaining = failure.retry_at.saturating_duration_since(Instant::now());
+            if !remaining.is_zero() {
+                return Err(eyre::Report::new(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!(
+                        "not reconnecting to {} for {:?} after {} failed attempts",
+                        addr, remaining, failure.failures
+                    ),
+                )));
+            }
+        }
+        match Self::open(this.clone(), addr).await {
+            Ok(connection) => {
+                self.failures.lock().unwrap().remove(&addr);
+                Ok(connection)
+            }
+            Err(err) => {
+                let failure = {
+                    let mut failures = self.failures.lock().unwrap();
+                    let count = failures.get(&addr).map_or(0, |failure| failure.failures) + 1;
+                    let retry_after = self.backoff.lock().unwrap().delay(count);
+                    let failure = ConnectFailure {
+                        failures: count,
+                        retry_after,
+                        retry_at: Instant::now() + retry_after,
+                    };
+                    failures.insert(addr, failure.clone());
+                    failure
+                };
+                tracing::debug!(
+                    "Connecting to {} failed {} times, backing off for {:?}: {:#}",
+                    addr,
+                    failure.failures,
+                    failure.retry_after,
+                    err
+                );
+                this.connection_hooks
+                    .lock()
+                    .unwrap()
+                    .connect_failed(addr, &failure);
+                Err(err)
+            }
+        }
+    }
+
+    async fn open(this: ThisClient, addr: SocketAddr) -> eyre::Result<Connection> {
+        tracing::trace!("Connecting TCP to address: {:?}", addr);
+        let stream = TcpStream::connect(addr)
+            .await
+            .context("failed to connect to tcp stream")?;
+        stream
+            .set_nodelay(true)
+            .context("failed to set nodelay for tcpstream")?;
+        let (reader, writer) = stream.into_split();
+        let stats = Arc::new(ConnectionStats::new());
+        let receiver = tokio::spawn(Client::loop_receiving_tcp_message(
+            this.clone(),
+            addr,
+            stats.clone(),
+            reader,
+        ));
+        this.connection_hooks.lock().unwrap().connected(addr);
+        Ok(Connection {
+            writer: Arc::new(Mutex::new(writer)),
+            stats,
+            receiver: Arc::new(receiver),
+        })
+    }
+
+    /// Removes the given dead connection, so that the next use of its address reconnects.
+    fn remove(&self, addr: SocketAddr, stats: &Arc<ConnectionStats>) {
+        let mut connections = self.connections.lock().unwrap();
+        let is_current = matches!(
+            connections.get(&addr).and_then(|cell| cell.get()),
+            Some(connection) if Arc::ptr_eq(&connection.stats, stats)
+        );
+        if is_current {
+            connections.remove(&addr);
+        }
+    }
+
+    /// Returns the established connections.
+    fn established(&self) -> Vec<(SocketAddr, Connection)> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(&addr, cell)| Some((addr, cell.get()?.clone())))
+            .collect()
+    }
+
+    /// Stops opening new connections and removes the established ones.
+    fn close(&self) -> Vec<(SocketAddr, Connection)> {
+        let mut connections = self.connections.lock().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+        connections
+            .drain()
+            .filter_map(|(addr, cell)| Some((addr, cell.get()?.clone())))
+            .collect()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+/// The reason why a TCP connection of a [`Client`] was closed.
+#[derive(Debug)]
+pub enum DisconnectReason {
+    /// The remote node closed the connection.
+    Closed,
+    /// Receiving from the connection failed.
+    Error(eyre::Report),
+    /// The client was shut down with [`Client::shutdown`].
+    Shutdown,
+}
+
+/// The backoff state of an address that a [`Client`] failed to connect to, see
+/// [`Client::on_connect_failure`].
+#[derive(Debug, Clone)]
+pub struct ConnectFailure {
+    /// The number of consecutive failed connection attempts.
+    pub failures: u32,
+    /// The time for which further connection attempts fail immediately.
+    pub retry_after: Duration,
+    retry_at: Instant,
+}
+
+/// Whether the address of the KVS thread serving a key was cached, see
+/// [`Client::get_lww_traced`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressCacheStatus {
+    /// The address was served from the cache.
+    Hit,
+    /// The address had to be queried from the routing tier.
+    Miss,
+}
+
+type ConnectHook = Box<dyn Fn(SocketAddr) + Send + Sync>;
+type DisconnectHook = Box<dyn Fn(SocketAddr, &DisconnectReason) + Send + Sync>;
+type ConnectFailureHook = Box<dyn Fn(SocketAddr, &ConnectFailure) + Send + Sync>;
+
+/// Callbacks that are invoked when TCP connections are opened, closed, or fail to open.
+#[derive(Default)]
+struct ConnectionHooks {
+    on_connect: Vec<ConnectHook>,
+    on_disconnect: Vec<DisconnectHook>,
+    on_connect_failure: Vec<ConnectFailureHook>,
+}
+
+impl ConnectionHooks {
+    fn connected(&self, addr: SocketAddr) {
+        for hook in &self.on_connect {
+            hook(addr);
+        }
+    }
+
+    fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) {
+        for hook in &self.on_disconnect {
+            hook(addr, reason);
+        }
+    }
+
+    fn connect_failed(&self, addr: SocketAddr, failure: &ConnectFailure) {
+        for hook in &self.on_connect_failure {
+            hook(addr, failure);
         }
     }
 }
@@ -82,59 +563,196 @@ impl ThisClient {
 impl Client {
     /// Create a new client node.
     pub fn new(config: ClientConfig) -> eyre::Result<Self> {
-        assert!(config.routing_threads > 0);
+        config.validate()?;
         let client_thread = ClientThread::new(format!("client-{}", uuid::Uuid::new_v4()), 0);
         let routing_threads: Vec<_> = (0..config.routing_threads)
             .map(|i| RoutingThread::new(i))
             .collect();
+        let key_address_cache =
+            KeyAddressCache::new(config.address_cache_capacity, config.address_cache_ttl);
         Ok(Self {
+            config,
             client_thread,
-            routing_ip: config.routing_ip,
-            routing_port_base: config.routing_port_base,
             routing_threads,
-            _timeout: config.timeout,
-            next_request_id: 1,
+            next_routing_thread: Default::default(),
+            next_request_id: Arc::new(AtomicU64::new(1)),
             kvs_tcp_address_cache: Default::default(),
-            key_address_cache: Default::default(),
-            tcp_write_halves: Default::default(),
+            key_address_cache: Arc::new(std::sync::Mutex::new(key_address_cache)),
+            kvs_latency: Default::default(),
+            failed_routers: Default::default(),
+            key_timeouts: Default::default(),
+            causal_clocks: Default::default(),
+            connections: Default::default(),
             address_response_promises: Default::default(),
             response_promises: Default::default(),
+            connection_hooks: Default::default(),
+            buffer_pool: Default::default(),
+            key_validator: None,
+            write_coalescing_window: Duration::ZERO,
+            pending_writes: Default::default(),
+            retry_policy: Default::default(),
+            metrics: Default::default(),
+            #[cfg(any(test, feature = "failure-injection"))]
+            failure_injection: Default::default(),
         })
     }
 
+    /// Returns the configuration this client was created with.
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Create a new client node and apply the configured [`AddressResolution`].
+    ///
+    /// With [`AddressResolution::Eager`], the addresses of the configured keys are resolved
+    /// and connections to their KVS threads are opened before this function returns.
+    pub async fn connect(config: ClientConfig) -> eyre::Result<Self> {
+        let address_resolution = config.address_resolution.clone();
+        let mut client = Self::new(config)?;
+        if let AddressResolution::Eager(keys) = address_resolution {
+            client.warm_up(keys).await?;
+        }
+        Ok(client)
+    }
+
+    /// Resolves the addresses of the given keys and opens connections to the KVS threads
+    /// serving them, so that later requests for these keys don't pay this cost.
+    pub async fn warm_up(&mut self, keys: Vec<ClientKey>) -> eyre::Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let request = self.make_address_request(keys)?;
+        let response = self.send_address_request(request).await?;
+        if let Some(error) = response.error {
+            return Err(eyre::Error::new(error).wrap_err("failed to resolve key addresses"));
+        }
+        let addrs: HashSet<_> = response.tcp_sockets.iter().map(|(_, addr)| *addr).collect();
+        self.handle_address_response(response)?;
+        for addr in addrs {
+            self.get_connection(addr).await?;
+        }
+        Ok(())
+    }
+
+    /// Registers a callback that is invoked whenever the client opens a TCP connection
+    /// to a routing or KVS node.
+    pub fn on_connect(&mut self, hook: impl Fn(SocketAddr) + Send + Sync + 'static) {
+        self.connection_hooks
+            .lock()
+            .unwrap()
+            .on_connect
+            .push(Box::new(hook));
+    }
+
+    /// Registers a callback that is invoked whenever a TCP connection of the client to a
+    /// routing or KVS node is closed.
+    pub fn on_disconnect(
+        &mut self,
+        hook: impl Fn(SocketAddr, &DisconnectReason) + Send + Sync + 'static,
+    ) {
+        self.connection_hooks
+            .lock()
+            .unwrap()
+            .on_disconnect
+            .push(Box::new(hook));
+    }
+
+    /// Registers a callback that is invoked whenever the client fails to open a TCP
+    /// connection to a routing or KVS node, with the resulting backoff of the address.
+    ///
+    /// See [`set_reconnect_backoff`][Self::set_reconnect_backoff].
+    pub fn on_connect_failure(
+        &mut self,
+        hook: impl Fn(SocketAddr, &ConnectFailure) + Send + Sync + 'static,
+    ) {
+        self.connection_hooks
+            .lock()
+            .unwrap()
+            .on_connect_failure
+            .push(Box::new(hook));
+    }
+
+    /// Sets the maximum number of idle serialization buffers kept for reuse.
+    ///
+    /// Reusing buffers reduces allocations when sending many requests. Setting this to
+    /// zero disables buffer reuse.
+    pub fn set_serialization_buffers(&mut self, max_buffers: usize) {
+        self.buffer_pool = BufferPool::new(max_buffers);
+    }
+
+    /// Returns a new request ID that is unique for the lifetime of the client.
+    ///
+    /// The IDs come from a 64-bit counter that doesn't wrap around during the lifetime
+    /// of the client, so the IDs of outstanding requests never collide and responses
+    /// are always delivered to the request that they belong to.
     fn gen_request_id(&mut self) -> String {
+        let next_request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
         let id = format!(
             "{}:{}_{}",
-            self.client_thread.node_id, self.client_thread.thread_id, self.next_request_id
+            self.client_thread.node_id, self.client_thread.thread_id, next_request_id
         );
-        log::trace!("Generated request ID: {}", id);
-        self.next_request_id = (self.next_request_id + 1) % 10000;
+        tracing::trace!("Generated request ID: {}", id);
         id
     }
 
-    fn make_address_request(&mut self, key: ClientKey) -> AddressRequest {
-        log::trace!("Making AddressRequest for key: {:?}", key);
-        AddressRequest {
+    /// Registers a validation hook that every key passes through before it is used.
+    ///
+    /// Keys rejected by the hook fail the operation with the returned error before any
+    /// network I/O happens. This can be used to enforce application rules such as a
+    /// maximum key length or a mandatory prefix.
+    pub fn set_key_validator(
+        &mut self,
+        validator: impl Fn(&ClientKey) -> eyre::Result<()> + Send + Sync + 'static,
+    ) {
+        self.key_validator = Some(Arc::new(validator));
+    }
+
+    fn validate_key(&self, key: &ClientKey) -> eyre::Result<()> {
+        match &self.key_validator {
+            Some(validator) => {
+                validator(key).with_context(|| format!("key {:?} failed validation", key))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn make_address_request(&mut self, keys: Vec<ClientKey>) -> eyre::Result<AddressRequest> {
+        tracing::trace!("Making AddressRequest for keys: {:?}", keys);
+        for key in &keys {
+            self.validate_key(key)?;
+        }
+        Ok(AddressRequest {
             request_id: self.gen_request_id(),
             response_address: self.client_thread.address_response_topic().to_string(),
-            keys: vec![key],
-        }
+            keys,
+        })
     }
 
-    fn make_request(&mut self, key: ClientKey, value: Option<LatticeValue>) -> ClientRequest {
-        log::trace!(
+    fn make_request(
+        &mut self,
+        key: ClientKey,
+        value: Option<LatticeValue>,
+    ) -> eyre::Result<ClientRequest> {
+        tracing::trace!(
             "Making ClientRequest for key: {:?}, value: {:?}",
             key,
             value
         );
-        ClientRequest {
+        self.validate_key(&key)?;
+        // report the number of replicas we know of, so the KVS can tell us if the
+        // cached addresses are stale
+        let mut address_cache_size = HashMap::new();
+        if let Some(threads) = self.key_address_cache.lock().unwrap().get(&key) {
+            address_cache_size.insert(key.clone(), threads.len());
+        }
+        Ok(ClientRequest {
             key,
             put_value: value,
             response_address: self.client_thread.response_topic().to_string(),
             request_id: self.gen_request_id(),
-            address_cache_size: HashMap::new(),
+            address_cache_size,
             timestamp: Instant::now(),
-        }
+        })
     }
 
     async fn make_address_response_promise(
@@ -146,7 +764,8 @@ impl Client {
             .lock()
             .await
             .insert(request_id, tx);
-        async { rx.await.map_err(Into::into) }
+        let connections = self.connections.clone();
+        async move { rx.await.map_err(|err| promise_error(&connections, err)) }
     }
 
     async fn make_response_promise(
@@ -155,92 +774,177 @@ impl Client {
     ) -> impl Future<Output = eyre::Result<Response>> {
         let (tx, rx) = oneshot::channel();
         self.response_promises.lock().await.insert(request_id, tx);
-        async { rx.await.map_err(Into::into) }
+        let connections = self.connections.clone();
+        async move { rx.await.map_err(|err| promise_error(&connections, err)) }
     }
 
-    fn get_routing_thread(&self) -> RoutingThread {
-        let mut rng = rand::thread_rng();
-        let thread = self
-            .routing_threads
-            .iter()
-            .choose(&mut rng)
-            .unwrap()
-            .clone();
-        log::trace!("Selected routing thread: {:?}", thread);
+    /// Selects the routing thread for an address request for the given keys, according
+    /// to the configured [`RoutingSelection`].
+    fn get_routing_thread(&self, keys: &[ClientKey]) -> RoutingThread {
+        let thread = match (self.config.routing_selection, keys.first()) {
+            (RoutingSelection::RoundRobin, _) => {
+                let index = self.next_routing_thread.fetch_add(1, Ordering::Relaxed);
+                self.routing_threads[index % self.routing_threads.len()].clone()
+            }
+            (RoutingSelection::HashByKey, Some(key)) => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                let index = hasher.finish() % self.routing_threads.len() as u64;
+                self.routing_threads[index as usize].clone()
+            }
+            (RoutingSelection::Random | RoutingSelection::HashByKey, _) => {
+                let mut rng = rand::thread_rng();
+                self.routing_threads
+                    .iter()
+                    .choose(&mut rng)
+                    .unwrap()
+                    .clone()
+            }
+        };
+        tracing::trace!("Selected routing thread: {:?}", thread);
         thread
     }
 
-    fn get_routing_tcp_address(&self) -> SocketAddr {
-        let routing_thread = self.get_routing_thread();
-        SocketAddr::new(
-            self.routing_ip,
-            self.routing_port_base + routing_thread.thread_id as u16,
-        )
+    /// Returns the tcp addresses of the selected routing thread on all routing nodes, in
+    /// the order in which they should be tried.
+    ///
+    /// Routing nodes are ordered as configured, except that nodes that failed within the
+    /// [`ROUTER_COOLDOWN`] come last, least recently failed first.
+    fn get_routing_tcp_addresses(&self, keys: &[ClientKey]) -> Vec<SocketAddr> {
+        let routing_thread = self.get_routing_thread(keys);
+        let port = self.config.routing_port_base + routing_thread.thread_id as u16;
+        let mut failed_routers = self.failed_routers.lock().unwrap();
+        failed_routers.retain(|_, failed_at| failed_at.elapsed() < ROUTER_COOLDOWN);
+        let mut ips: Vec<_> = std::iter::once(self.config.routing_ip)
+            .chain(self.config.fallback_routing_ips.iter().copied())
+            .collect();
+        // stable, so live routing nodes keep their configured order
+        ips.sort_by_key(|ip| failed_routers.get(ip).copied());
+        ips.into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect()
     }
 
     async fn loop_receiving_tcp_message(
         this: ThisClient,
+        addr: SocketAddr,
+        stats: Arc<ConnectionStats>,
         mut reader: tcp::OwnedReadHalf,
-    ) -> eyre::Result<()> {
-        loop {
-            // TODO: handle error
-            let message = receive_tcp_message(&mut reader).await?;
-            if let Some(message) = message {
-                match message {
-                    TcpMessage::AddressResponse(response) => {
-                        if let Some(tx) = this
-                            .address_response_promises
-                            .lock()
-                            .await
-                            .remove(&response.response_id)
-                        {
-                            tx.send(response).unwrap();
-                        } else {
-                            // TODO: update address cache
-                            log::warn!("Unexpected AddressResponse: {:?}", response);
+    ) {
+        let reason = loop {
+            let message = match receive_tcp_message(&mut reader).await {
+                Ok(Some(message)) => message,
+                Ok(None) => break DisconnectReason::Closed,
+                Err(err) => break DisconnectReason::Error(err),
+            };
+            match message {
+                TcpMessage::AddressResponse(response) => {
+                    stats.received_response();
+                    if let Some(tx) = this
+                        .address_response_promises
+                        .lock()
+                        .await
+                        .remove(&response.response_id)
+                    {
+                        if tx.send(response).is_err() {
+                            tracing::trace!("AddressResponse receiver was dropped");
                         }
+                    } else {
+                        // TODO: update address cache
+                        this.metrics.record_orphan_response();
+                        tracing::warn!(
+                            "Received AddressResponse {} from {} with no waiting request \
+                            (timed out, cancelled, or unknown ID): {:?}",
+                            response.response_id,
+                            addr,
+                            response
+                        );
                     }
-                    TcpMessage::Response(response) => {
-                        if let Some(response_id) = response.response_id.as_ref() {
-                            if let Some(tx) =
-                                this.response_promises.lock().await.remove(response_id)
-                            {
-                                tx.send(response).unwrap();
-                            }
-                        } else {
-                            log::warn!("Unexpected Response: {:?}", response);
+                }
+                TcpMessage::Response(response) => {
+                    stats.received_response();
+                    let tx = match response.response_id.as_ref() {
+                        Some(response_id) => {
+                            this.response_promises.lock().await.remove(response_id)
+                        }
+                        None => None,
+                    };
+                    if let Some(tx) = tx {
+                        if tx.send(response).is_err() {
+                            tracing::trace!("Response receiver was dropped");
                         }
+                    } else {
+                        this.metrics.record_orphan_response();
+                        let keys: Vec<_> = response.tuples.iter().map(|tuple| &tuple.key).collect();
+                        tracing::warn!(
+                            "Received Response {:?} for keys {:?} from {} with no waiting \
+                            request (timed out, cancelled, or unknown ID)",
+                            response.response_id,
+                            keys,
+                            addr
+                        );
                     }
-                    other => panic!("unexpected tcp message {:?}", other),
                 }
+                other => tracing::warn!("Unexpected tcp message from {}: {:?}", addr, other),
             }
-        }
-        // TODO: recycle dead connection
+        };
+        tracing::trace!("TCP connection to {} closed: {:?}", addr, reason);
+        this.connections.remove(addr, &stats);
+        this.connection_hooks
+            .lock()
+            .unwrap()
+            .disconnected(addr, &reason);
     }
 
-    async fn get_tcp_writer(
-        &mut self,
-        addr: SocketAddr,
-    ) -> eyre::Result<Arc<Mutex<tcp::OwnedWriteHalf>>> {
-        Ok(match self.tcp_write_halves.entry(addr) {
-            std::collections::hash_map::Entry::Occupied(entry) => entry.get().clone(),
-            std::collections::hash_map::Entry::Vacant(entry) => {
-                log::trace!("Connecting TCP to address: {:?}", addr);
-                let stream = TcpStream::connect(addr)
-                    .await
-                    .context("failed to connect to tcp stream")?;
-                stream
-                    .set_nodelay(true)
-                    .context("failed to set nodelay for tcpstream")?;
-                let (reader, writer) = stream.into_split();
-                let writer = entry.insert(Arc::new(Mutex::new(writer))).clone();
-                tokio::spawn(Self::loop_receiving_tcp_message(
-                    ThisClient::from(self),
-                    reader,
-                ));
-                writer
+    async fn get_connection(&self, addr: SocketAddr) -> eyre::Result<Connection> {
+        self.connections.get(ThisClient::from(self), addr).await
+    }
+
+    /// Shuts down the client, including all other handles of it.
+    ///
+    /// The writes held back by the write coalescing window of this handle are sent
+    /// first. Then the receive tasks are stopped and the TCP connections are closed.
+    /// Requests that are still waiting for a response fail with a "client is shutting
+    /// down" error, and later requests through other handles fail immediately.
+    ///
+    /// Returns the error of sending the held back writes, if any.
+    pub async fn shutdown(mut self) -> eyre::Result<()> {
+        let flushed = self.flush_writes().await;
+        for (addr, connection) in self.connections.close() {
+            connection.receiver.abort();
+            if let Err(err) = connection.writer.lock().await.shutdown().await {
+                tracing::trace!("Failed to shut down TCP connection to {}: {}", addr, err);
             }
-        })
+            self.connection_hooks
+                .lock()
+                .unwrap()
+                .disconnected(addr, &DisconnectReason::Shutdown);
+        }
+        // dropping the senders wakes up the waiting requests
+        self.address_response_promises.lock().await.clear();
+        self.response_promises.lock().await.clear();
+        flushed.context("failed to send held back writes")
+    }
+
+    /// Returns diagnostic information about the open TCP connections of this client.
+    pub fn connection_info(&self) -> Vec<ConnectionInfo> {
+        self.connections
+            .established()
+            .into_iter()
+            .map(|(addr, connection)| ConnectionInfo {
+                addr,
+                kvs_threads: self
+                    .kvs_tcp_address_cache
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, thread_addr)| **thread_addr == addr)
+                    .map(|(thread, _)| thread.clone())
+                    .collect(),
+                last_used: *connection.stats.last_used.lock().unwrap(),
+                in_flight: connection.stats.in_flight.load(Ordering::Relaxed),
+            })
+            .collect()
     }
 
     async fn send_tcp_message(
@@ -248,38 +952,119 @@ impl Client {
         addr: SocketAddr,
         message: TcpMessage,
     ) -> eyre::Result<()> {
-        let writer = self.get_tcp_writer(addr).await?;
-        let mut writer = writer.lock().await;
-        send_tcp_message(&message, &mut writer).await
+        let connection = self
+            .get_connection(addr)
+            .await
+            .with_context(|| format!("failed to connect to {}", addr))?;
+        let mut writer = connection.writer.lock().await;
+        connection.stats.sent(&message);
+        #[cfg(any(test, feature = "failure-injection"))]
+        match self.failure_injection.inject(addr, &message).await? {
+            failure_injection::Injected::Send => {}
+            failure_injection::Injected::Drop => {
+                tracing::debug!("Dropping tcp message to {}: {:?}", addr, message);
+                return Ok(());
+            }
+            failure_injection::Injected::Corrupt => {
+                tracing::debug!("Corrupting tcp message to {}: {:?}", addr, message);
+                return failure_injection::send_corrupted(&mut writer).await;
+            }
+        }
+        let mut buf = self.buffer_pool.take();
+        let result = send_tcp_message_buffered(&message, &mut writer, &mut buf).await;
+        self.buffer_pool.put(buf);
+        result.with_context(|| format!("failed to send tcp message to {}", addr))
     }
 
+    /// Sends the address request, retrying it according to the [`RetryPolicy`] if it
+    /// fails to be sent or times out.
     async fn send_address_request(
+        &mut self,
+        mut request: AddressRequest,
+    ) -> eyre::Result<AddressResponse> {
+        let mut attempt = 1;
+        loop {
+            match self.send_address_request_once(request.clone()).await {
+                Err(err) if attempt < self.retry_policy.max_attempts => {
+                    let delay = self.retry_policy.delay(attempt);
+                    tracing::debug!("Retrying address request in {:?}: {:#}", delay, err);
+                    tokio::time::sleep(delay).await;
+                    request.request_id = self.gen_request_id();
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_address_request_once(
         &mut self,
         request: AddressRequest,
     ) -> eyre::Result<AddressResponse> {
         let request_id = request.request_id.clone();
-        let addr = self.get_routing_tcp_address();
-        let promise = self.make_address_response_promise(request_id).await;
-        self.send_tcp_message(addr, TcpMessage::AddressRequest(request))
-            .await?;
-        promise.await.map_err(Into::into)
+        let addrs = self.get_routing_tcp_addresses(&request.keys);
+        let promise = self.make_address_response_promise(request_id.clone()).await;
+        let mut result = Err(eyre!("no routing node configured"));
+        for addr in addrs {
+            result = self
+                .send_tcp_message(addr, TcpMessage::AddressRequest(request.clone()))
+                .await
+                .map(|()| addr);
+            match &result {
+                Ok(_) => {
+                    self.failed_routers.lock().unwrap().remove(&addr.ip());
+                    break;
+                }
+                Err(err) => {
+                    tracing::warn!("Failed to reach routing node {}: {:#}", addr, err);
+                    self.failed_routers
+                        .lock()
+                        .unwrap()
+                        .insert(addr.ip(), Instant::now());
+                }
+            }
+        }
+        let addr = match result {
+            Ok(addr) => addr,
+            Err(err) => {
+                self.address_response_promises
+                    .lock()
+                    .await
+                    .remove(&request_id);
+                return Err(err.wrap_err(format!("failed to send address request {}", request_id)));
+            }
+        };
+        match tokio::time::timeout(self.config.timeout, promise).await {
+            Ok(response) => response.with_context(|| {
+                format!(
+                    "failed to receive address response for request {} from {}",
+                    request_id, addr
+                )
+            }),
+            Err(_) => {
+                self.address_response_promises
+                    .lock()
+                    .await
+                    .remove(&request_id);
+                Err(eyre::Report::new(ClientError::Timeout(format!(
+                    "address request {} to {} after {:?}",
+                    request_id, addr, self.config.timeout
+                ))))
+            }
+        }
     }
 
     fn handle_address_response(&mut self, response: AddressResponse) -> eyre::Result<()> {
-        response
-            .tcp_sockets
-            .into_iter()
-            .for_each(|(kvs_thread, addr)| {
-                self.kvs_tcp_address_cache.insert(kvs_thread, addr);
-            });
+        self.kvs_tcp_address_cache
+            .lock()
+            .unwrap()
+            .extend(response.tcp_sockets);
 
+        let mut key_address_cache = self.key_address_cache.lock().unwrap();
         for key_addr in response.addresses {
             let key = key_addr.key;
             for node in key_addr.nodes {
-                self.key_address_cache
-                    .entry(key.clone())
-                    .or_default()
-                    .insert(node);
+                key_address_cache.insert(key.clone(), node);
             }
         }
 
@@ -288,173 +1073,2619 @@ impl Client {
 
     /// Make and send an AddressRequest for the given key,
     /// and update the address cache with the response.
+    ///
+    /// If the routing tier reports an error, the cached addresses of the key are
+    /// invalidated and the [`AnnaError`] is returned, wrapped with context.
     async fn query_key_address(&mut self, key: &ClientKey) -> eyre::Result<()> {
-        log::trace!("Querying address for key: {:?}", key);
-        let request = self.make_address_request(key.clone());
-        let response = self.send_address_request(request).await?;
-        assert!(response.error.is_none()); // TODO: handle the error (cache invalidation, no server, etc.)
+        tracing::trace!("Querying address for key: {:?}", key);
+        let request = self.make_address_request(vec![key.clone()])?;
+        // retried as part of the request that needs the address
+        let response = self.send_address_request_once(request).await?;
+        if let Some(error) = response.error {
+            self.invalidate_key_address(key);
+            return Err(eyre::Error::new(error)
+                .wrap_err(format!("routing tier failed to resolve key {:?}", key)));
+        }
         self.handle_address_response(response)?;
         Ok(())
     }
 
+    /// Removes the cached KVS threads of the given key and their tcp addresses.
+    fn invalidate_key_address(&mut self, key: &ClientKey) {
+        let threads = self.key_address_cache.lock().unwrap().remove(key);
+        let mut kvs_tcp_address_cache = self.kvs_tcp_address_cache.lock().unwrap();
+        for thread in threads.into_iter().flatten() {
+            kvs_tcp_address_cache.remove(&thread);
+        }
+    }
+
+    /// Queries the routing tier for the addresses of the given keys and returns the raw
+    /// response.
+    ///
+    /// This bypasses the address cache: the cache is neither consulted nor updated, and
+    /// any error reported by the routing node is returned as part of the response.
+    /// It is intended for topology inspection and monitoring tools.
+    pub async fn query_addresses(&mut self, keys: Vec<ClientKey>) -> eyre::Result<AddressResponse> {
+        let request = self.make_address_request(keys)?;
+        self.send_address_request(request).await
+    }
+
+    /// Checks that the routing node is reachable and returns the round-trip time.
+    ///
+    /// Sends an address request for a sentinel key. The response is discarded, so the
+    /// address cache is not affected. Errors if the routing node can't be reached or
+    /// doesn't respond within the configured timeout; requests are not retried.
+    pub async fn ping(&mut self) -> eyre::Result<Duration> {
+        // bypasses the key validator, which may reject the sentinel key
+        let request = AddressRequest {
+            request_id: self.gen_request_id(),
+            response_address: self.client_thread.address_response_topic().to_string(),
+            keys: vec![PING_KEY.into()],
+        };
+        let start = Instant::now();
+        self.send_address_request_once(request)
+            .await
+            .context("failed to ping routing node")?;
+        Ok(start.elapsed())
+    }
+
     fn get_kvs_thread_from_cache(&self, key: &ClientKey) -> Option<KvsThread> {
         let mut rng = rand::thread_rng();
-        let addr_set = self.key_address_cache.get(key);
-        if let Some(addr_set) = addr_set {
-            addr_set.iter().choose(&mut rng).cloned()
+        let mut key_address_cache = self.key_address_cache.lock().unwrap();
+        if let Some(addr_set) = key_address_cache.get(key) {
+            match self.config.replica_selection {
+                ReplicaSelection::LatencyWeighted => {
+                    choose_replica(addr_set, &self.kvs_latency.lock().unwrap(), &mut rng)
+                }
+                ReplicaSelection::HashByKey => choose_replica_by_key(addr_set, key),
+            }
         } else {
             None
         }
     }
 
-    async fn get_kvs_thread(&mut self, key: &ClientKey) -> eyre::Result<Option<KvsThread>> {
+    /// Returns the tcp address of a KVS thread serving the key, without querying the
+    /// routing tier.
+    fn get_cached_key_tcp_address(&self, key: &ClientKey) -> Option<SocketAddr> {
+        let kvs_thread = self.get_kvs_thread_from_cache(key)?;
+        let kvs_tcp_address_cache = self.kvs_tcp_address_cache.lock().unwrap();
+        kvs_tcp_address_cache.get(&kvs_thread).copied()
+    }
+
+    /// Returns the current response latency estimate of each KVS thread.
+    ///
+    /// The estimates are exponentially weighted moving averages over the responses
+    /// received from each thread. Replica selection favors threads with lower estimates.
+    pub fn kvs_latency_estimates(&self) -> HashMap<KvsThread, Duration> {
+        self.kvs_latency.lock().unwrap().clone()
+    }
+
+    fn record_kvs_latency(&mut self, kvs_thread: KvsThread, latency: Duration) {
+        self.kvs_latency
+            .lock()
+            .unwrap()
+            .entry(kvs_thread)
+            .and_modify(|estimate| {
+                *estimate = estimate.mul_f64(1.0 - LATENCY_EWMA_WEIGHT)
+                    + latency.mul_f64(LATENCY_EWMA_WEIGHT)
+            })
+            .or_insert(latency);
+    }
+
+    /// Selects a KVS thread serving the key, querying the routing tier if none is cached.
+    ///
+    /// Also returns whether the thread was served from the cache.
+    async fn get_kvs_thread(
+        &mut self,
+        key: &ClientKey,
+    ) -> eyre::Result<Option<(KvsThread, AddressCacheStatus)>> {
         let thread = match self.get_kvs_thread_from_cache(key) {
-            thread @ Some(_) => thread, // cache hit
+            Some(thread) => {
+                // cache hit
+                self.metrics.record_address_lookup(true);
+                Some((thread, AddressCacheStatus::Hit))
+            }
             None => {
                 // cache miss
+                self.metrics.record_address_lookup(false);
                 self.query_key_address(key).await?;
                 self.get_kvs_thread_from_cache(key)
+                    .map(|thread| (thread, AddressCacheStatus::Miss))
             }
         };
-        log::trace!("Selected kvs thread: {:?}, key: {:?}", thread, key);
+        tracing::trace!("Selected kvs thread: {:?}, key: {:?}", thread, key);
         Ok(thread)
     }
 
-    async fn get_key_tcp_address(&mut self, key: &ClientKey) -> eyre::Result<Option<SocketAddr>> {
-        let kvs_thread = match self.get_kvs_thread(key).await? {
+    /// Returns the tcp addresses of all known replicas of the key, in random order.
+    async fn get_key_tcp_addresses(&mut self, key: &ClientKey) -> eyre::Result<Vec<SocketAddr>> {
+        if self.get_kvs_thread(key).await?.is_none() {
+            return Ok(Vec::new());
+        }
+        let mut addrs: Vec<_> = {
+            let mut key_address_cache = self.key_address_cache.lock().unwrap();
+            let kvs_tcp_address_cache = self.kvs_tcp_address_cache.lock().unwrap();
+            key_address_cache
+                .get(key)
+                .into_iter()
+                .flatten()
+                .filter_map(|thread| kvs_tcp_address_cache.get(thread))
+                .cloned()
+                .collect()
+        };
+        addrs.sort();
+        addrs.dedup();
+        addrs.shuffle(&mut rand::thread_rng());
+        Ok(addrs)
+    }
+
+    /// Selects a KVS thread serving the key and returns its tcp address.
+    ///
+    /// The status is a hit only if both the thread and its address were cached.
+    async fn get_key_tcp_address(
+        &mut self,
+        key: &ClientKey,
+    ) -> eyre::Result<Option<(KvsThread, SocketAddr, AddressCacheStatus)>> {
+        let (kvs_thread, mut status) = match self.get_kvs_thread(key).await? {
             Some(thread) => thread,
             None => return Ok(None),
         };
-        let addr = match self.kvs_tcp_address_cache.get(&kvs_thread) {
+        let cached_addr = self
+            .kvs_tcp_address_cache
+            .lock()
+            .unwrap()
+            .get(&kvs_thread)
+            .cloned();
+        let addr = match cached_addr {
             addr @ Some(_) => addr, // cache hit
             None => {
                 // cache miss
+                status = AddressCacheStatus::Miss;
                 self.query_key_address(key).await?;
-                self.kvs_tcp_address_cache.get(&kvs_thread)
+                self.kvs_tcp_address_cache
+                    .lock()
+                    .unwrap()
+                    .get(&kvs_thread)
+                    .cloned()
             }
-        }
-        .cloned();
-        log::trace!("Got kvs tcp address: {:?}, thread: {:?}", addr, kvs_thread);
-        Ok(addr)
+        };
+        tracing::trace!("Got kvs tcp address: {:?}, thread: {:?}", addr, kvs_thread);
+        Ok(addr.map(|addr| (kvs_thread, addr, status)))
     }
 
+    /// Sends the request to a KVS thread serving its key, retrying it according to the
+    /// [`RetryPolicy`] if it fails.
+    ///
+    /// Before each retry, the cached addresses of the key are invalidated, so that they
+    /// are queried from the routing tier again.
     async fn send_request(&mut self, request: ClientRequest) -> eyre::Result<Response> {
-        let request_id = request.request_id.clone();
-        let addr = self
-            .get_key_tcp_address(&request.key)
-            .await?
-            .context("fail to get tcp address of the kvs thread the key locates")?;
-        let promise = self.make_response_promise(request_id).await;
-        self.send_tcp_message(addr, TcpMessage::Request(request.into()))
-            .await?;
-        promise.await.map_err(Into::into)
+        let (response, _) = self.send_request_traced(request).await?;
+        Ok(response)
     }
 
-    async fn put_lattice(&mut self, key: ClientKey, value: LatticeValue) -> eyre::Result<()> {
-        let request = self.make_request(key.clone(), Some(value));
-        let response = self.send_request(request).await?;
-        // TODO: handle error
-        assert!(response.error.is_ok());
-        assert!(response.tuples.len() == 1);
-        assert!(response.tuples[0].error.is_none());
-        Ok(())
-    }
-
-    async fn get_lattice(&mut self, key: ClientKey) -> eyre::Result<LatticeValue> {
-        let request = self.make_request(key.clone(), None);
-        let response = self.send_request(request).await?;
-
-        // TODO: handle cache invalidation and other special errors
-        if response.error.is_err() {
-            return Err(response.error.unwrap_err().into());
+    /// Like [`send_request`][Self::send_request], but also returns whether the address
+    /// used by the attempt that got the response was cached.
+    async fn send_request_traced(
+        &mut self,
+        mut request: ClientRequest,
+    ) -> eyre::Result<(Response, AddressCacheStatus)> {
+        let mut attempt = 1;
+        loop {
+            match self.send_request_once(request.clone(), attempt).await {
+                Err(err) if attempt < self.retry_policy.max_attempts => {
+                    let delay = self.retry_policy.delay(attempt);
+                    tracing::debug!(
+                        "Retrying request for key {:?} in {:?}: {:#}",
+                        request.key,
+                        delay,
+                        err
+                    );
+                    self.invalidate_key_address(&request.key);
+                    tokio::time::sleep(delay).await;
+                    request.request_id = self.gen_request_id();
+                    attempt += 1;
+                }
+                result => return result,
+            }
         }
+    }
 
-        let response_tuple = response
-            .tuples
-            .get(0)
-            .cloned()
-            .ok_or_else(|| eyre!("response has no tuples"))?;
-        if let Some(error) = response_tuple.error {
-            Err(error.into())
-        } else {
-            response_tuple.lattice.context("expected lattice value")
+    async fn send_request_once(
+        &mut self,
+        request: ClientRequest,
+        attempt: u32,
+    ) -> eyre::Result<(Response, AddressCacheStatus)> {
+        let key = request.key.clone();
+        let span = tracing::trace_span!(
+            "send_request",
+            request_id = %request.request_id,
+            key = ?key,
+            attempt,
+            kvs_thread = tracing::field::Empty,
+        );
+        let request_span = span.clone();
+        async move {
+            let (kvs_thread, addr, status) = self
+                .get_key_tcp_address(&key)
+                .instrument(tracing::trace_span!("resolve_address", key = ?key))
+                .await?
+                .with_context(|| {
+                    format!(
+                        "fail to get tcp address of the kvs thread the key {:?} locates",
+                        key
+                    )
+                })?;
+            request_span.record("kvs_thread", tracing::field::debug(&kvs_thread));
+            let promise = self.send_request_to(addr, request).await?;
+            let start = Instant::now();
+            let response = match promise.await {
+                Ok(response) => {
+                    self.key_timeouts.lock().unwrap().remove(&key);
+                    response
+                }
+                Err(err) => {
+                    if matches!(ClientError::from(&err), ClientError::Timeout(_)) {
+                        self.record_key_timeout(&key);
+                    }
+                    return Err(err);
+                }
+            };
+            tracing::trace!(latency = ?start.elapsed(), "Received response");
+            self.record_kvs_latency(kvs_thread, start.elapsed());
+            self.handle_invalidations(&response);
+            Ok((response, status))
         }
+        .instrument(span)
+        .await
     }
 
-    /// Try to put a *last writer wins* value with the given key.
-    pub async fn put_lww(&mut self, key: ClientKey, value: Vec<u8>) -> eyre::Result<()> {
-        self.put_lattice(
-            key,
-            LatticeValue::Lww(LastWriterWinsLattice::from_pair(Timestamp::now(), value)),
-        )
-        .await
+    /// Counts a timed out request for the key and invalidates its cached addresses once
+    /// [`ClientConfig::address_refresh_timeouts`] consecutive requests timed out.
+    fn record_key_timeout(&mut self, key: &ClientKey) {
+        let threshold = match self.config.address_refresh_timeouts {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        let mut key_timeouts = self.key_timeouts.lock().unwrap();
+        let timeouts = key_timeouts.entry(key.clone()).or_default();
+        *timeouts += 1;
+        if *timeouts >= threshold {
+            tracing::debug!(
+                "{} consecutive requests for key {:?} timed out, refreshing its addresses",
+                timeouts,
+                key
+            );
+            key_timeouts.remove(key);
+            drop(key_timeouts);
+            self.invalidate_key_address(key);
+        }
     }
 
-    /// Try to get a *last writer wins* value with the given key.
-    pub async fn get_lww(&mut self, key: ClientKey) -> eyre::Result<Vec<u8>> {
-        Ok(self
-            .get_lattice(key)
-            .await?
-            .into_lww()?
-            .into_revealed()
-            .into_value())
+    /// Invalidates the cached addresses of all keys that the KVS flagged as stale.
+    ///
+    /// The addresses are queried from the routing tier again on the next request.
+    fn handle_invalidations(&mut self, response: &Response) {
+        for tuple in response.tuples.iter().filter(|tuple| tuple.invalidate) {
+            if let Key::Client(key) = &tuple.key {
+                tracing::debug!("Cached addresses of key {:?} are stale", key);
+                self.invalidate_key_address(key);
+            }
+        }
     }
 
-    /// Begin a transaction that satisfies *read committed* isolation level.
-    pub fn begin_transaction(&mut self) -> ReadCommittedTransaction {
+    /// Sends the request to the KVS thread at the given address.
+    ///
+    /// Returns a future that resolves to the response of the request, or fails if no
+    /// response arrives within the configured timeout.
+    async fn send_request_to(
+        &mut self,
+        addr: SocketAddr,
+        request: ClientRequest,
+    ) -> eyre::Result<impl Future<Output = eyre::Result<Response>>> {
+        let request_id = request.request_id.clone();
+        let key = request.key.clone();
+        let created = request.timestamp;
+        let promise = self.make_response_promise(request_id.clone()).await;
+        self.metrics.record_request();
+        if let Err(err) = self
+            .send_tcp_message(addr, TcpMessage::Request(request.into()))
+            .await
+        {
+            self.response_promises.lock().await.remove(&request_id);
+            self.metrics.record_error(&ClientError::from(&err));
+            return Err(err.wrap_err(format!(
+                "failed to send request {} for key {:?} to {}",
+                request_id, key, addr
+            )));
+        }
+        let timeout = self.config.timeout;
+        let response_promises = self.response_promises.clone();
+        let metrics = self.metrics.clone();
+        Ok(async move {
+            match tokio::time::timeout(timeout, promise).await {
+                Ok(Ok(response)) => {
+                    metrics.record_latency(created.elapsed());
+                    let errors = response
+                        .error
+                        .err()
+                        .into_iter()
+                        .chain(response.tuples.iter().filter_map(|tuple| tuple.error));
+                    for error in errors {
+                        metrics.record_error(&error.into());
+                    }
+                    Ok(response)
+                }
+                Ok(Err(err)) => Err(err.wrap_err(format!(
+                    "failed to receive response for request {} (key {:?}) from {}",
+                    request_id, key, addr
+                ))),
+                Err(_) => {
+                    response_promises.lock().await.remove(&request_id);
+                    let err = ClientError::Timeout(format!(
+                        "request {} (key {:?}) to {} after {:?}",
+                        request_id, key, addr, timeout
+                    ));
+                    metrics.record_error(&err);
+                    Err(eyre::Report::new(err))
+                }
+            }
+        })
+    }
+
+    /// Sets the window during which successive writes to the same key are coalesced.
+    ///
+    /// With a non-zero window, a write is held back for up to `window` and later writes
+    /// to the same key within that time are merged into it locally, so that only a
+    /// single request is sent. Held back writes are sent by the first operation after the
+    /// window elapsed, before a read of the same key, or by
+    /// [`flush_writes`][Self::flush_writes]. Errors of held back writes are reported by
+    /// the operation that sends them, and writes that are still pending when the client
+    /// is dropped are lost.
+    ///
+    /// The window defaults to zero, which sends every write immediately.
+    pub fn set_write_coalescing_window(&mut self, window: Duration) {
+        self.write_coalescing_window = window;
+    }
+
+    /// Sends all writes that are held back by the write coalescing window.
+    pub async fn flush_writes(&mut self) -> Result<(), ClientError> {
+        let keys: Vec<_> = self.pending_writes.keys().cloned().collect();
+        for key in keys {
+            self.flush_pending_write(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the held back write of the given key, if any.
+    async fn flush_pending_write(&mut self, key: &ClientKey) -> eyre::Result<()> {
+        match self.pending_writes.remove(key) {
+            Some(pending) => self.send_put(key.clone(), pending.value).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the held back writes whose coalescing window elapsed.
+    async fn flush_expired_writes(&mut self) -> eyre::Result<()> {
+        let window = self.write_coalescing_window;
+        let expired: Vec<_> = self
+            .pending_writes
+            .iter()
+            .filter(|(_, pending)| pending.since.elapsed() >= window)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.flush_pending_write(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Puts an arbitrary lattice value with the given key.
+    ///
+    /// The KVS merges the value into the stored one, which fails if the stored value
+    /// has a different lattice type. This is the building block of the typed methods
+    /// such as [`put_lww`][Self::put_lww] and can be used for lattice types that they
+    /// don't cover.
+    ///
+    /// The payloads of *last writer wins* values are encoded like those of `put_lww`,
+    /// i.e. large values are compressed if [`ClientConfig::compression`] is set.
+    pub async fn put_lattice(
+        &mut self,
+        key: ClientKey,
+        value: LatticeValue,
+    ) -> Result<(), ClientError> {
+        let value = compression::encode_lattice(self.config.compression, value);
+        self.flush_expired_writes().await?;
+        if self.write_coalescing_window.is_zero() {
+            return Ok(self.send_put(key, value).await?);
+        }
+        self.validate_key(&key)?;
+        if let Some(pending) = self.pending_writes.get_mut(&key) {
+            if pending.value.try_merge(&value).is_ok() {
+                tracing::trace!("Coalesced write to key {:?}", key);
+                return Ok(());
+            }
+            self.flush_pending_write(&key).await?;
+        }
+        self.pending_writes.insert(
+            key,
+            PendingWrite {
+                value,
+                since: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn send_put(&mut self, key: ClientKey, value: LatticeValue) -> eyre::Result<()> {
+        let request = self.make_request(key.clone(), Some(value))?;
+        let response = self.send_request(request).await?;
+        if let Err(error) = response.error {
+            return Err(eyre::Error::new(error).wrap_err(format!("failed to put key {:?}", key)));
+        }
+        let mut tuples = response.tuples;
+        if tuples.len() != 1 {
+            bail!("expected one response tuple, got {}", tuples.len());
+        }
+        match tuples.remove(0).error {
+            Some(error) => {
+                Err(eyre::Error::new(error).wrap_err(format!("failed to put key {:?}", key)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the raw lattice value of the given key.
+    ///
+    /// Unlike the typed methods such as [`get_lww`][Self::get_lww], this returns the
+    /// value regardless of its lattice type. Callers have to handle all
+    /// [`LatticeValue`] variants themselves. Compressed *last writer wins* values are
+    /// decompressed.
+    pub async fn get_lattice(&mut self, key: ClientKey) -> Result<LatticeValue, ClientError> {
+        self.flush_expired_writes().await?;
+        self.flush_pending_write(&key).await?;
+        let request = self.make_request(key.clone(), None)?;
+        let response = self.send_request(request).await?;
+        Ok(lattice_from_response(response)?)
+    }
+
+    /// Try to put a *last writer wins* value with the given key.
+    ///
+    /// Large values are compressed if [`ClientConfig::compression`] is set.
+    pub async fn put_lww(&mut self, key: ClientKey, value: Vec<u8>) -> Result<(), ClientError> {
+        self.put_lww_versioned(key, value).await?;
+        Ok(())
+    }
+
+    /// Like [`put_lww`][Self::put_lww], but returns the timestamp of the written value.
+    ///
+    /// The timestamp is assigned by the client and decides which of several writes to
+    /// the key wins: the KVS keeps the value with the latest timestamp.
+    pub async fn put_lww_versioned(
+        &mut self,
+        key: ClientKey,
+        value: Vec<u8>,
+    ) -> Result<Timestamp, ClientError> {
+        let timestamp = Timestamp::now();
+        self.put_lattice(
+            key,
+            LatticeValue::Lww(LastWriterWinsLattice::from_pair(timestamp, value)),
+        )
+        .await?;
+        Ok(timestamp)
+    }
+
+    /// Encodes the given value according to the configured [`Compression`].
+    fn compress(&self, value: Vec<u8>) -> Vec<u8> {
+        compression::encode(self.config.compression, value)
+    }
+
+    /// Try to get a *last writer wins* value with the given key.
+    ///
+    /// If the key stores a different lattice type, e.g. because it is being migrated,
+    /// a best-effort conversion is attempted: a set lattice, or the value set of a causal
+    /// lattice, is accepted if it contains exactly one element. Other values result in
+    /// an error.
+    pub async fn get_lww(&mut self, key: ClientKey) -> Result<Vec<u8>, ClientError> {
+        Ok(lww_from_lattice(self.get_lattice(key).await?)?)
+    }
+
+    /// Like [`get_lww`][Self::get_lww], but also returns the timestamp of the value, see
+    /// [`put_lww_versioned`][Self::put_lww_versioned].
+    ///
+    /// Unlike `get_lww`, only *last writer wins* values are accepted, since other
+    /// lattice types have no timestamp.
+    pub async fn get_lww_versioned(
+        &mut self,
+        key: ClientKey,
+    ) -> Result<(Timestamp, Vec<u8>), ClientError> {
+        match self.get_lattice(key.clone()).await? {
+            LatticeValue::Lww(lattice) => {
+                let pair = lattice.into_revealed();
+                let timestamp = pair.timestamp();
+                Ok((timestamp, pair.into_value()))
+            }
+            _ => Err(ClientError::Lattice(format!(
+                "value of key {:?} is not a last writer wins value",
+                key
+            ))),
+        }
+    }
+
+    /// Like [`get_lww`][Self::get_lww], but also reports whether the address of the KVS
+    /// thread serving the key was cached or had to be queried from the routing tier.
+    ///
+    /// The status reflects the address lookup of the attempt that got the response;
+    /// retries invalidate the cached addresses and always query the routing tier again.
+    pub async fn get_lww_traced(
+        &mut self,
+        key: ClientKey,
+    ) -> Result<(Vec<u8>, AddressCacheStatus), ClientError> {
+        self.flush_expired_writes().await?;
+        self.flush_pending_write(&key).await?;
+        let request = self.make_request(key, None)?;
+        let (response, status) = self.send_request_traced(request).await?;
+        Ok((lww_from_lattice(lattice_from_response(response)?)?, status))
+    }
+
+    /// Returns whether a value of any lattice type is stored under the given key.
+    ///
+    /// A missing key is reported as `Ok(false)`; only transport and protocol errors
+    /// result in an error.
+    pub async fn exists(&mut self, key: ClientKey) -> Result<bool, ClientError> {
+        match self.get_lattice(key).await {
+            Ok(_) => Ok(true),
+            Err(ClientError::KeyDoesNotExist) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Puts the given lattice value only if no value is stored under the key yet.
+    ///
+    /// Returns whether the value was written. This can be used to initialize a key with
+    /// an empty lattice of any type, e.g. a set, without merging into an existing value.
+    ///
+    /// The existence check and the write are separate requests, so a concurrent writer
+    /// can still create the key in between. The values are then merged as usual.
+    pub async fn put_if_absent(
+        &mut self,
+        key: ClientKey,
+        value: LatticeValue,
+    ) -> Result<bool, ClientError> {
+        if self.exists(key.clone()).await? {
+            return Ok(false);
+        }
+        self.put_lattice(key, value).await?;
+        Ok(true)
+    }
+
+    /// Try to get a *last writer wins* value with the given key, reading `fallback`
+    /// if the key does not exist.
+    ///
+    /// Only a missing `primary` key triggers the fallback read; other errors are returned
+    /// as is. If neither key exists, the [`AnnaError::KeyDoesNotExist`] error of the
+    /// fallback read is returned.
+    pub async fn get_lww_with_fallback(
+        &mut self,
+        primary: ClientKey,
+        fallback: ClientKey,
+    ) -> Result<Vec<u8>, ClientError> {
+        match self.get_lww(primary).await {
+            Err(ClientError::KeyDoesNotExist) => self.get_lww(fallback).await,
+            result => result,
+        }
+    }
+
+    /// Try to get the *last writer wins* values of multiple keys.
+    ///
+    /// The addresses of all keys that are not cached are resolved in a single address
+    /// request, and the requests for the individual keys are sent concurrently. Errors
+    /// are reported per key in the returned map, classified as [`ClientError`]s so that
+    /// e.g. missing keys can be told apart from timeouts.
+    ///
+    /// All keys are read with [`ConsistencyLevel::Eventual`], see
+    /// [`get_lww_many_with_consistency`][Self::get_lww_many_with_consistency] to read
+    /// some of them from a quorum of replicas.
+    pub async fn get_lww_many(
+        &mut self,
+        keys: Vec<ClientKey>,
+    ) -> Result<HashMap<ClientKey, Result<Vec<u8>, ClientError>>, ClientError> {
+        let keys = keys
+            .into_iter()
+            .map(|key| (key, ConsistencyLevel::Eventual))
+            .collect();
+        self.get_lww_many_with_consistency(keys).await
+    }
+
+    /// Try to get the *last writer wins* values of multiple keys, each with its own
+    /// [`ConsistencyLevel`].
+    ///
+    /// This behaves like [`get_lww_many`][Self::get_lww_many], but the keys with
+    /// [`ConsistencyLevel::Quorum`] are read from all known replicas and their value is
+    /// returned once a majority of them answered, merged by timestamp. The reads of all
+    /// keys are sent concurrently, so the cheap eventual reads are not delayed by the
+    /// quorum reads. If a key is requested more than once, the strongest level is used.
+    pub async fn get_lww_many_with_consistency(
+        &mut self,
+        keys: Vec<(ClientKey, ConsistencyLevel)>,
+    ) -> Result<HashMap<ClientKey, Result<Vec<u8>, ClientError>>, ClientError> {
+        let mut values = HashMap::new();
+        let mut pending: Vec<(ClientKey, ConsistencyLevel)> = Vec::new();
+        for (key, level) in keys {
+            if values.contains_key(&key) {
+                continue;
+            }
+            if let Some((_, pending_level)) = pending.iter_mut().find(|(other, _)| *other == key) {
+                *pending_level = (*pending_level).max(level);
+                continue;
+            }
+            match self.validate_key(&key) {
+                Ok(()) => pending.push((key, level)),
+                Err(err) => {
+                    values.insert(key, Err(err.into()));
+                }
+            }
+        }
+
+        let misses: Vec<_> = pending
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| self.get_cached_key_tcp_address(key).is_none())
+            .cloned()
+            .collect();
+        if !misses.is_empty() {
+            let request = self.make_address_request(misses)?;
+            let response = self.send_address_request(request).await?;
+            if let Some(error) = response.error {
+                return Err(eyre::Error::new(error)
+                    .wrap_err("failed to resolve key addresses")
+                    .into());
+            }
+            self.handle_address_response(response)?;
+        }
+
+        let mut reads = Vec::new();
+        for (key, level) in pending {
+            let read = async {
+                self.flush_pending_write(&key).await?;
+                let addrs = match level {
+                    ConsistencyLevel::Eventual => {
+                        self.get_cached_key_tcp_address(&key).into_iter().collect()
+                    }
+                    ConsistencyLevel::Quorum => self.get_key_tcp_addresses(&key).await?,
+                };
+                if addrs.is_empty() {
+                    bail!(
+                        "fail to get tcp address of the kvs thread the key {:?} locates",
+                        key
+                    );
+                }
+                let needed = match level {
+                    ConsistencyLevel::Eventual => 1,
+                    ConsistencyLevel::Quorum => addrs.len() / 2 + 1,
+                };
+                let mut promises = Vec::new();
+                let mut failed = Vec::new();
+                for addr in addrs {
+                    let request = self.make_request(key.clone(), None)?;
+                    let request_id = request.request_id.clone();
+                    match self.send_request_to(addr, request).await {
+                        Ok(promise) => promises.push((request_id, promise)),
+                        Err(err) => failed.push(Err(err)),
+                    }
+                }
+                Ok((needed, promises, failed))
+            }
+            .await;
+            match read {
+                Ok(read) => reads.push((key, read)),
+                Err(err) => {
+                    values.insert(key, Err(err.into()));
+                }
+            }
+        }
+        let (keys, reads): (Vec<_>, Vec<_>) = reads.into_iter().unzip();
+        let responses = future::join_all(reads.into_iter().map(|(needed, promises, failed)| {
+            let response_promises = self.response_promises.clone();
+            async move {
+                let mut responses = failed;
+                responses.extend(collect_responses(response_promises, promises, needed).await);
+                (needed, responses)
+            }
+        }))
+        .await;
+        for (key, (needed, responses)) in keys.into_iter().zip(responses) {
+            let value = self
+                .merge_read_responses(responses, needed)
+                .and_then(lww_from_lattice)
+                .map_err(ClientError::from);
+            values.insert(key, value);
+        }
+        Ok(values)
+    }
+
+    /// Merges the lattices of the responses to a read that required `needed` responses.
+    ///
+    /// Missing keys count as responses, but only fail the read if none of the responses
+    /// contains a value.
+    fn merge_read_responses(
+        &mut self,
+        responses: Vec<eyre::Result<Response>>,
+        needed: usize,
+    ) -> eyre::Result<LatticeValue> {
+        let mut received = 0;
+        let mut merged: Option<LatticeValue> = None;
+        let mut first_error = None;
+        for response in responses {
+            let lattice = response.and_then(|response| {
+                self.handle_invalidations(&response);
+                received += 1;
+                lattice_from_response(response)
+            });
+            match (lattice, &mut merged) {
+                (Ok(lattice), Some(merged)) => merged.try_merge(&lattice)?,
+                (Ok(lattice), None) => merged = Some(lattice),
+                (Err(err), _) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match merged {
+            Some(merged) if received >= needed => Ok(merged),
+            _ => Err(first_error
+                .unwrap_or_else(|| eyre!("received {} of {} responses", received, needed))),
+        }
+    }
+
+    /// Try to get a *last writer wins* value with the given key, hedging the read
+    /// across replicas.
+    ///
+    /// The read is first sent to one replica of the key. If no response arrives within
+    /// `hedge_after`, a duplicate read is sent to another replica and whichever response
+    /// arrives first is returned. This reduces tail latency for replicated keys. If only
+    /// one replica of the key is known, this behaves like [`get_lww`][Self::get_lww].
+    pub async fn get_lww_hedged(
+        &mut self,
+        key: ClientKey,
+        hedge_after: Duration,
+    ) -> Result<Vec<u8>, ClientError> {
+        self.flush_pending_write(&key).await?;
+        let mut addrs = self.get_key_tcp_addresses(&key).await?.into_iter();
+        let first_addr = addrs.next().with_context(|| {
+            format!(
+                "fail to get tcp address of the kvs thread the key {:?} locates",
+                key
+            )
+        })?;
+        let first_request = self.make_request(key.clone(), None)?;
+        let first_id = first_request.request_id.clone();
+        let first_response = self.send_request_to(first_addr, first_request).await?;
+        futures::pin_mut!(first_response);
+
+        let response = match addrs.next() {
+            None => first_response.await?,
+            Some(second_addr) => {
+                match tokio::time::timeout(hedge_after, &mut first_response).await {
+                    Ok(response) => response?,
+                    Err(_) => {
+                        tracing::trace!(
+                            "Hedging read of key {:?} to second replica at {}",
+                            key,
+                            second_addr
+                        );
+                        let second_request = self.make_request(key.clone(), None)?;
+                        let second_id = second_request.request_id.clone();
+                        let second_response =
+                            self.send_request_to(second_addr, second_request).await?;
+                        futures::pin_mut!(second_response);
+                        let (response, loser_id) =
+                            match future::select(first_response, second_response).await {
+                                Either::Left((response, _)) => (response, second_id),
+                                Either::Right((response, _)) => (response, first_id),
+                            };
+                        // the cancelled request should not leave a dangling promise
+                        self.response_promises.lock().await.remove(&loser_id);
+                        response?
+                    }
+                }
+            }
+        };
+        Ok(lww_from_lattice(lattice_from_response(response)?)?)
+    }
+
+    /// Try to put a *last writer wins* value with the given key on all known replicas.
+    ///
+    /// Returns the number of replicas that acknowledged the write within the configured
+    /// timeout. An error is returned only if no replica acknowledged the write.
+    pub async fn put_lww_acked(
+        &mut self,
+        key: ClientKey,
+        value: Vec<u8>,
+    ) -> Result<usize, ClientError> {
+        self.flush_pending_write(&key).await?;
+        let addrs = self.get_key_tcp_addresses(&key).await?;
+        if addrs.is_empty() {
+            return Err(eyre!(
+                "fail to get tcp address of the kvs thread the key {:?} locates",
+                key
+            )
+            .into());
+        }
+        let replicas = addrs.len();
+        let value = self.compress(value);
+        let lattice = LatticeValue::Lww(LastWriterWinsLattice::from_pair(Timestamp::now(), value));
+
+        let mut promises = Vec::new();
+        for addr in addrs {
+            let request = self.make_request(key.clone(), Some(lattice.clone()))?;
+            match self.send_request_to(addr, request).await {
+                Ok(promise) => promises.push(promise),
+                Err(err) => tracing::warn!("Failed to send write to replica: {:?}", err),
+            }
+        }
+        let acks = future::join_all(promises)
+            .await
+            .into_iter()
+            .filter(|result| matches!(result, Ok(response) if put_succeeded(response)))
+            .count();
+        if acks == 0 {
+            return Err(eyre!(
+                "none of the {} replicas acknowledged the write of key {:?}",
+                replicas,
+                key
+            )
+            .into());
+        }
+        Ok(acks)
+    }
+
+    /// Try to get a *last writer wins* value with the given key as a list of
+    /// newline-delimited records.
+    ///
+    /// The newline separators are not included in the records. A trailing newline
+    /// does not produce an empty final record.
+    pub async fn get_lww_lines(&mut self, key: ClientKey) -> Result<Vec<Vec<u8>>, ClientError> {
+        let value = self.get_lww(key).await?;
+        Ok(split_lines(&value))
+    }
+
+    /// Append a line to the *last writer wins* value with the given key.
+    ///
+    /// A trailing newline is added to the line automatically. If the key does not
+    /// exist yet, it is created.
+    pub async fn append_line(&mut self, key: ClientKey, line: Vec<u8>) -> Result<(), ClientError> {
+        let mut tx = self.begin_transaction();
+        let mut value = match tx.get(key.clone()).await {
+            Ok(value) => value,
+            Err(err) if key_does_not_exist(&err) => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        if !value.is_empty() && !value.ends_with(b"\n") {
+            value.push(b'\n');
+        }
+        value.extend(line);
+        value.push(b'\n');
+        tx.put(key, value).await?;
+        Ok(tx.commit().await?)
+    }
+
+    /// Begin a transaction that satisfies *read committed* isolation level.
+    pub fn begin_transaction(&mut self) -> ReadCommittedTransaction {
         ReadCommittedTransaction::new(self)
     }
 
     /// Try to put a set value with the given key.
-    pub async fn put_set(&mut self, key: ClientKey, set: HashSet<Vec<u8>>) -> eyre::Result<()> {
+    pub async fn put_set(
+        &mut self,
+        key: ClientKey,
+        set: HashSet<Vec<u8>>,
+    ) -> Result<(), ClientError> {
         self.put_lattice(key, LatticeValue::Set(SetLattice::new(set)))
             .await
     }
 
+    /// Try to merge the given set into the set value of the given key, returning the
+    /// merged set.
+    ///
+    /// The KVS does not echo merged values in PUT responses, so the merged set is read
+    /// back after the PUT succeeded. The result may include elements that other clients
+    /// added concurrently.
+    pub async fn put_set_get(
+        &mut self,
+        key: ClientKey,
+        set: HashSet<Vec<u8>>,
+    ) -> Result<HashSet<Vec<u8>>, ClientError> {
+        self.put_set(key.clone(), set).await?;
+        self.get_set(key).await
+    }
+
     /// Try to get a set value with the given key.
-    pub async fn get_set(&mut self, key: ClientKey) -> eyre::Result<HashSet<Vec<u8>>> {
-        Ok(self.get_lattice(key).await?.into_set()?.into_revealed())
+    ///
+    /// If the key stores a different lattice type, e.g. because it is being migrated,
+    /// a best-effort conversion is attempted: a *last writer wins* value is returned as
+    /// a single-element set, and the value set of a causal lattice is returned as is.
+    /// Other values result in an error.
+    pub async fn get_set(&mut self, key: ClientKey) -> Result<HashSet<Vec<u8>>, ClientError> {
+        Ok(set_from_lattice(self.get_lattice(key).await?)?)
+    }
+
+    /// Try to get a set value with the given key, sorted by the element bytes.
+    ///
+    /// Unlike [`get_set`][Self::get_set], the order of the returned elements is
+    /// deterministic.
+    pub async fn get_set_sorted(&mut self, key: ClientKey) -> Result<Vec<Vec<u8>>, ClientError> {
+        let mut elements: Vec<_> = self.get_set(key).await?.into_iter().collect();
+        elements.sort();
+        Ok(elements)
     }
 
     /// Try to put a *multi-key causal* value with the given key.
-    pub async fn put_causal(&mut self, key: ClientKey, value: Vec<u8>) -> eyre::Result<()> {
-        // construct a test client id - version pair
+    ///
+    /// The write is tagged with the latest vector clock that this client knows for the
+    /// key, advanced by one for this client. It therefore supersedes all versions that
+    /// the client has read or written before, while versions written concurrently by
+    /// other clients are kept and merged by the KVS. Dependencies on other keys are not
+    /// tracked.
+    pub async fn put_causal(&mut self, key: ClientKey, value: Vec<u8>) -> Result<(), ClientError> {
         let vector_clock = {
-            let mut vector_clock = VectorClock::default();
-            vector_clock.insert("test".into(), MaxLattice::new(1));
-            vector_clock
-        };
-        // construct one test dependencies
-        let dependencies = {
-            let mut dependencies = MapLattice::default();
-            dependencies.insert("dep1".into(), {
-                let mut clock = VectorClock::default();
-                clock.insert("test1".into(), MaxLattice::new(1));
-                clock
-            });
-            dependencies
+            let mut clocks = self.causal_clocks.lock().unwrap();
+            let clock = clocks.entry(key.clone()).or_default();
+            let version = clock
+                .reveal()
+                .get(&self.client_thread.node_id)
+                .map_or(0, |version| *version.reveal());
+            clock.insert(
+                self.client_thread.node_id.clone(),
+                MaxLattice::new(version + 1),
+            );
+            clock.clone()
         };
-        // populate the value
         let value = {
-            let mut map = SetLattice::default();
-            map.insert(value);
-            map
+            let mut set = SetLattice::default();
+            set.insert(value);
+            set
         };
-        let mkcp = MultiKeyCausalPayload::new(vector_clock, dependencies, value);
+        let mkcp = MultiKeyCausalPayload::new(vector_clock, MapLattice::default(), value);
         let mkcl = MultiKeyCausalLattice::new(mkcp);
 
         self.put_lattice(key, LatticeValue::MultiCausal(mkcl)).await
     }
 
     /// Try to get a *multi-key causal* value with the given key.
+    ///
+    /// The value set contains all concurrent versions of the key. Its vector clock is
+    /// remembered, so that the next [`put_causal`][Self::put_causal] of the key
+    /// supersedes them.
     pub async fn get_causal(
         &mut self,
         key: ClientKey,
-    ) -> eyre::Result<MultiKeyCausalPayload<SetLattice<Vec<u8>>>> {
-        Ok(self
-            .get_lattice(key)
+    ) -> Result<MultiKeyCausalPayload<SetLattice<Vec<u8>>>, ClientError> {
+        let payload = self
+            .get_lattice(key.clone())
             .await?
             .into_multi_causal()?
-            .into_revealed())
+            .into_revealed();
+        self.causal_clocks
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .merge(&payload.vector_clock);
+        Ok(payload)
+    }
+}
+
+/// The weight of a new sample in the per-thread latency estimates.
+const LATENCY_EWMA_WEIGHT: f64 = 0.2;
+
+/// The time after which a routing node that could not be connected to is preferred
+/// again according to its configured order.
+const ROUTER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Returns the error of a response promise whose sender was dropped.
+fn promise_error(connections: &ConnectionPool, err: oneshot::error::RecvError) -> eyre::Report {
+    if connections.is_closed() {
+        eyre!("client is shutting down")
+    } else {
+        err.into()
+    }
+}
+
+/// The key whose address is requested by [`Client::ping`].
+const PING_KEY: &str = "__anna_client_ping__";
+
+/// Chooses a replica at random, weighted by the inverse of its latency estimate.
+///
+/// Replicas without an estimate are weighted like the fastest known replica, so that
+/// they get a chance to be measured.
+fn choose_replica<R: rand::Rng>(
+    replicas: &HashSet<KvsThread>,
+    latencies: &HashMap<KvsThread, Duration>,
+    rng: &mut R,
+) -> Option<KvsThread> {
+    let weight = |latency: &Duration| 1.0 / latency.as_secs_f64().max(1e-6);
+    let replicas: Vec<_> = replicas.iter().collect();
+    let max_weight = replicas
+        .iter()
+        .filter_map(|thread| latencies.get(thread))
+        .map(weight)
+        .fold(None, |max: Option<f64>, w| {
+            Some(max.map_or(w, |max| max.max(w)))
+        });
+    let max_weight = match max_weight {
+        Some(max_weight) => max_weight,
+        None => return replicas.into_iter().choose(rng).cloned(),
+    };
+    replicas
+        .choose_weighted(rng, |thread| {
+            latencies.get(thread).map_or(max_weight, weight)
+        })
+        .ok()
+        .map(|thread| (*thread).clone())
+}
+
+/// Chooses the replica with the highest hash of the key and the replica.
+fn choose_replica_by_key(replicas: &HashSet<KvsThread>, key: &ClientKey) -> Option<KvsThread> {
+    replicas
+        .iter()
+        .max_by_key(|thread| {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            thread.hash(&mut hasher);
+            hasher.finish()
+        })
+        .cloned()
+}
+
+/// Returns whether the given error reports that the requested key does not exist.
+///
+/// The whole error chain is inspected, so the condition is still detected when the
+/// [`AnnaError`] or [`ClientError`] was wrapped with additional context or as the source
+/// of another error.
+pub(crate) fn key_does_not_exist(err: &eyre::Report) -> bool {
+    err.chain().any(|err| {
+        matches!(err.downcast_ref(), Some(AnnaError::KeyDoesNotExist))
+            || matches!(err.downcast_ref(), Some(ClientError::KeyDoesNotExist))
+    })
+}
+
+/// Splits the given value into newline-delimited records.
+fn split_lines(value: &[u8]) -> Vec<Vec<u8>> {
+    let value = value.strip_suffix(b"\n").unwrap_or(value);
+    if value.is_empty() {
+        return Vec::new();
+    }
+    value.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect()
+}
+
+/// Converts the given lattice to a *last writer wins* value.
+///
+/// Sets and the value sets of causal lattices are accepted if they contain exactly
+/// one element.
+fn lww_from_lattice(lattice: LatticeValue) -> eyre::Result<Vec<u8>> {
+    let set = match lattice {
+        LatticeValue::Lww(lattice) => return Ok(lattice.into_revealed().into_value()),
+        LatticeValue::Set(set) => set.into_revealed(),
+        LatticeValue::SingleCausal(lattice) => lattice.into_revealed().value.into_revealed(),
+        LatticeValue::MultiCausal(lattice) => lattice.into_revealed().value.into_revealed(),
+        LatticeValue::OrderedSet(_) => {
+            bail!("cannot read an ordered set as a last writer wins value")
+        }
+    };
+    if set.len() != 1 {
+        bail!(
+            "cannot read a set of {} elements as a last writer wins value",
+            set.len()
+        );
+    }
+    tracing::debug!("Read a single-element set as a last writer wins value");
+    Ok(set.into_iter().next().unwrap())
+}
+
+/// Converts the given lattice to a set value.
+///
+/// *Last writer wins* values are returned as single-element sets and causal lattices
+/// are reduced to their value set.
+fn set_from_lattice(lattice: LatticeValue) -> eyre::Result<HashSet<Vec<u8>>> {
+    match lattice {
+        LatticeValue::Set(set) => Ok(set.into_revealed()),
+        LatticeValue::Lww(lattice) => {
+            tracing::debug!("Read a last writer wins value as a set");
+            Ok([lattice.into_revealed().into_value()].into_iter().collect())
+        }
+        LatticeValue::SingleCausal(lattice) => Ok(lattice.into_revealed().value.into_revealed()),
+        LatticeValue::MultiCausal(lattice) => Ok(lattice.into_revealed().value.into_revealed()),
+        LatticeValue::OrderedSet(_) => bail!("cannot read an ordered set as a set value"),
+    }
+}
+
+/// Returns whether the given PUT response reports success.
+fn put_succeeded(response: &Response) -> bool {
+    response.error.is_ok()
+        && !response.tuples.is_empty()
+        && response.tuples.iter().all(|tuple| tuple.error.is_none())
+}
+
+/// Awaits the given response promises until `needed` of them succeeded or all of them
+/// finished.
+///
+/// The promises of the requests that are still outstanding are removed from
+/// `response_promises`, so that their late responses are not kept around.
+async fn collect_responses<F>(
+    response_promises: Arc<Mutex<HashMap<String, oneshot::Sender<Response>>>>,
+    promises: Vec<(String, F)>,
+    needed: usize,
+) -> Vec<eyre::Result<Response>>
+where
+    F: Future<Output = eyre::Result<Response>>,
+{
+    let mut outstanding: HashSet<_> = promises.iter().map(|(id, _)| id.clone()).collect();
+    let mut promises: FuturesUnordered<_> = promises
+        .into_iter()
+        .map(|(id, promise)| async move { (id, promise.await) })
+        .collect();
+    let mut responses = Vec::new();
+    let mut succeeded = 0;
+    while succeeded < needed {
+        let (id, response) = match promises.next().await {
+            Some(response) => response,
+            None => break,
+        };
+        outstanding.remove(&id);
+        if response.is_ok() {
+            succeeded += 1;
+        }
+        responses.push(response);
+    }
+    if !outstanding.is_empty() {
+        let mut response_promises = response_promises.lock().await;
+        for id in outstanding {
+            response_promises.remove(&id);
+        }
+    }
+    responses
+}
+
+/// Extracts the lattice value of the single key in the given GET response.
+///
+/// Compressed *last writer wins* values are decompressed.
+fn lattice_from_response(response: Response) -> eyre::Result<LatticeValue> {
+    // TODO: handle other special errors
+    if response.error.is_err() {
+        return Err(response.error.unwrap_err().into());
+    }
+
+    let response_tuple = response
+        .tuples
+        .get(0)
+        .cloned()
+        .ok_or_else(|| eyre!("response has no tuples"))?;
+    if let Some(error) = response_tuple.error {
+        Err(error.into())
+    } else {
+        compression::decode_lattice(response_tuple.lattice.context("expected lattice value")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        messages::{request::KeyOperation, response::ResponseTuple, KeyAddress, Request},
+        nodes::send_tcp_message,
+        store::LatticeValueStore,
+    };
+
+    pub(crate) fn test_config() -> ClientConfig {
+        ClientConfig {
+            routing_ip: "127.0.0.1".parse().unwrap(),
+            fallback_routing_ips: Vec::new(),
+            routing_port_base: 12340,
+            routing_threads: 1,
+            timeout: Duration::from_secs(1),
+            address_resolution: AddressResolution::Lazy,
+            address_cache_capacity: None,
+            address_cache_ttl: None,
+            address_refresh_timeouts: None,
+            routing_selection: RoutingSelection::Random,
+            replica_selection: ReplicaSelection::LatencyWeighted,
+            compression: None,
+        }
+    }
+
+    /// Returns a local address that nothing is listening on.
+    pub(crate) async fn dead_addr() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    /// Returns a local address that accepts connections but never responds.
+    pub(crate) async fn silent_addr() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        addr
+    }
+
+    /// A minimal in-process KVS thread that serves GET and PUT requests over TCP.
+    pub(crate) struct MockKvs {
+        pub addr: SocketAddr,
+        pub store: Arc<std::sync::Mutex<LatticeValueStore<Key>>>,
+        pub requests: Arc<AtomicUsize>,
+        /// The `address_cache_size` of the last request.
+        pub address_cache_size: Arc<std::sync::Mutex<HashMap<ClientKey, usize>>>,
+        /// Whether to flag the client's cached addresses as stale in every response.
+        pub invalidate: Arc<AtomicBool>,
+    }
+
+    impl MockKvs {
+        /// Starts a mock KVS that answers every request after the given delay.
+        pub async fn start(delay: Duration) -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let store: Arc<std::sync::Mutex<LatticeValueStore<Key>>> = Default::default();
+            let requests: Arc<AtomicUsize> = Default::default();
+            let address_cache_size: Arc<std::sync::Mutex<HashMap<ClientKey, usize>>> =
+                Default::default();
+            let invalidate: Arc<AtomicBool> = Default::default();
+            let store_clone = store.clone();
+            let requests_clone = requests.clone();
+            let address_cache_size_clone = address_cache_size.clone();
+            let invalidate_clone = invalidate.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let store = store_clone.clone();
+                    let requests = requests_clone.clone();
+                    let address_cache_size = address_cache_size_clone.clone();
+                    let invalidate = invalidate_clone.clone();
+                    stream.set_nodelay(true).unwrap();
+                    tokio::spawn(async move {
+                        let (mut reader, mut writer) = stream.into_split();
+                        while let Ok(Some(TcpMessage::Request(request))) =
+                            receive_tcp_message(&mut reader).await
+                        {
+                            requests.fetch_add(1, Ordering::SeqCst);
+                            *address_cache_size.lock().unwrap() =
+                                request.address_cache_size.clone();
+                            tokio::time::sleep(delay).await;
+                            let mut response = Self::handle(&store, request);
+                            if invalidate.load(Ordering::SeqCst) {
+                                for tuple in &mut response.tuples {
+                                    tuple.invalidate = true;
+                                }
+                            }
+                            let message = TcpMessage::Response(response);
+                            if send_tcp_message(&message, &mut writer).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+            });
+            Self {
+                addr,
+                store,
+                requests,
+                address_cache_size,
+                invalidate,
+            }
+        }
+
+        fn handle(store: &std::sync::Mutex<LatticeValueStore<Key>>, request: Request) -> Response {
+            let mut response = request.new_response();
+            let mut store = store.lock().unwrap();
+            for operation in request.request.into_tuples() {
+                let key = operation.key().clone();
+                let (lattice, error) = match operation {
+                    KeyOperation::Get(key) => match store.get(&key) {
+                        Some(value) => (Some(value.clone()), None),
+                        None => (None, Some(AnnaError::KeyDoesNotExist)),
+                    },
+                    KeyOperation::Put(tuple) => (None, store.put(tuple.key, tuple.value).err()),
+                };
+                response.tuples.push(ResponseTuple {
+                    key,
+                    lattice,
+                    error,
+                    invalidate: false,
+                });
+            }
+            response
+        }
+
+        /// Stores a *last writer wins* value directly in the mock's store.
+        pub fn put_lww(&self, key: &str, value: &[u8]) {
+            self.store
+                .lock()
+                .unwrap()
+                .put(
+                    Key::Client(key.into()),
+                    LatticeValue::Lww(LastWriterWinsLattice::from_pair(
+                        Timestamp::now(),
+                        value.to_vec(),
+                    )),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Starts a mock routing node that reports every requested key as served by `nodes`.
+    ///
+    /// Returns the port of the routing node.
+    pub(crate) async fn mock_routing(nodes: Vec<(KvsThread, SocketAddr)>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let nodes = nodes.clone();
+                stream.set_nodelay(true).unwrap();
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.into_split();
+                    while let Ok(Some(TcpMessage::AddressRequest(request))) =
+                        receive_tcp_message(&mut reader).await
+                    {
+                        // report an error if no KVS nodes are configured
+                        let error = nodes.is_empty().then_some(AnnaError::NoServers);
+                        let response = AddressResponse {
+                            addresses: request
+                                .keys
+                                .into_iter()
+                                .map(|key| KeyAddress {
+                                    key,
+                                    nodes: nodes.iter().map(|(thread, _)| thread.clone()).collect(),
+                                })
+                                .collect(),
+                            error,
+                            response_id: request.request_id,
+                            tcp_sockets: nodes.clone(),
+                        };
+                        let message = TcpMessage::AddressResponse(response);
+                        if send_tcp_message(&message, &mut writer).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    /// Makes the client believe that `key` is served by a KVS thread at `addr`.
+    pub(crate) fn cache_key_address(client: &mut Client, key: &ClientKey, addr: SocketAddr) {
+        let kvs_thread = KvsThread {
+            node_id: format!("kvs-{}", addr),
+            thread_id: 0,
+        };
+        client
+            .key_address_cache
+            .lock()
+            .unwrap()
+            .insert(key.clone(), kvs_thread.clone());
+        client
+            .kvs_tcp_address_cache
+            .lock()
+            .unwrap()
+            .insert(kvs_thread, addr);
+    }
+
+    #[tokio::test]
+    async fn send_error_contains_target() {
+        let addr = dead_addr().await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "foo".into();
+        cache_key_address(&mut client, &key, addr);
+
+        let err = client.get_lww(key.clone()).await.unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains(&addr.to_string()), "{}", message);
+        assert!(message.contains(&format!("{:?}", key)), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn hedged_read_prefers_fast_replica() {
+        let slow = MockKvs::start(Duration::from_secs(2)).await;
+        slow.put_lww("key", b"slow");
+        let fast = MockKvs::start(Duration::ZERO).await;
+        fast.put_lww("key", b"fast");
+
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, slow.addr);
+        cache_key_address(&mut client, &key, fast.addr);
+
+        let start = Instant::now();
+        let value = client
+            .get_lww_hedged(key, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(value, b"fast");
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(client.response_promises.lock().await.is_empty());
+    }
+
+    #[test]
+    fn split_lines_handles_trailing_newline() {
+        assert!(split_lines(b"").is_empty());
+        assert_eq!(split_lines(b"a\nb"), vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(split_lines(b"a\nb\n"), vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(
+            split_lines(b"a\n\nb"),
+            vec![b"a".to_vec(), vec![], b"b".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn append_and_read_lines() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "log".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        for line in ["first", "second", "third"] {
+            client
+                .append_line(key.clone(), line.as_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        let lines = client.get_lww_lines(key).await.unwrap();
+        assert_eq!(
+            lines,
+            vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn query_addresses_returns_raw_response() {
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 3,
+        };
+        let kvs_addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let port = mock_routing(vec![(kvs_thread.clone(), kvs_addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+
+        let keys: Vec<ClientKey> = vec!["a".into(), "b".into()];
+        let response = client.query_addresses(keys.clone()).await.unwrap();
+        assert!(response.error.is_none());
+        assert_eq!(response.tcp_sockets, vec![(kvs_thread.clone(), kvs_addr)]);
+        for (address, key) in response.addresses.iter().zip(keys) {
+            assert_eq!(address.key, key);
+            assert_eq!(address.nodes, vec![kvs_thread.clone()]);
+        }
+        assert!(client.key_address_cache.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ping_measures_round_trip() {
+        let port = mock_routing(Vec::new()).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+        let rtt = client.ping().await.unwrap();
+        assert!(rtt > Duration::ZERO && rtt < client.config.timeout);
+        assert!(client.key_address_cache.lock().unwrap().is_empty());
+
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: silent_addr().await.port(),
+            timeout: Duration::from_millis(100),
+            ..test_config()
+        })
+        .unwrap();
+        let start = Instant::now();
+        let err = client.ping().await.unwrap_err();
+        assert!(matches!(ClientError::from(err), ClientError::Timeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn replica_selection_favors_fast_replica() {
+        let slow = MockKvs::start(Duration::from_millis(50)).await;
+        slow.put_lww("key", b"value");
+        let fast = MockKvs::start(Duration::ZERO).await;
+        fast.put_lww("key", b"value");
+
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, slow.addr);
+        cache_key_address(&mut client, &key, fast.addr);
+
+        // warm up until both replicas have a latency estimate
+        while client.kvs_latency_estimates().len() < 2 {
+            client.get_lww(key.clone()).await.unwrap();
+        }
+        let slow_before = slow.requests.load(Ordering::SeqCst);
+        for _ in 0..20 {
+            client.get_lww(key.clone()).await.unwrap();
+        }
+        let slow_requests = slow.requests.load(Ordering::SeqCst) - slow_before;
+        assert!(
+            slow_requests <= 2,
+            "{} requests to slow replica",
+            slow_requests
+        );
+    }
+
+    #[tokio::test]
+    async fn replica_selection_by_key_is_stable() {
+        let mut kvs = Vec::new();
+        let mut client = Client::new(ClientConfig {
+            replica_selection: ReplicaSelection::HashByKey,
+            ..test_config()
+        })
+        .unwrap();
+        let key: ClientKey = "key".into();
+        for _ in 0..3 {
+            let replica = MockKvs::start(Duration::ZERO).await;
+            replica.put_lww("key", b"value");
+            cache_key_address(&mut client, &key, replica.addr);
+            kvs.push(replica);
+        }
+
+        let thread = client.get_kvs_thread_from_cache(&key).unwrap();
+        for _ in 0..10 {
+            assert_eq!(client.get_kvs_thread_from_cache(&key).unwrap(), thread);
+            client.get_lww(key.clone()).await.unwrap();
+        }
+        let requests: Vec<_> = kvs
+            .iter()
+            .map(|replica| replica.requests.load(Ordering::SeqCst))
+            .collect();
+        assert_eq!(requests.iter().filter(|&&count| count > 0).count(), 1);
+        assert_eq!(requests.iter().sum::<usize>(), 10);
+    }
+
+    #[tokio::test]
+    async fn sorted_set_is_deterministic() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "set".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        let set: HashSet<Vec<u8>> = ["c", "a", "b", "ab"]
+            .iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
+        client.put_set(key.clone(), set).await.unwrap();
+
+        let expected: Vec<Vec<u8>> = ["a", "ab", "b", "c"]
+            .iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
+        for _ in 0..3 {
+            assert_eq!(client.get_set_sorted(key.clone()).await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn eager_resolution_connects_upfront() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread.clone(), kvs.addr)]).await;
+        let client = Client::connect(ClientConfig {
+            routing_port_base: port,
+            address_resolution: AddressResolution::Eager(vec!["a".into()]),
+            ..test_config()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            client.key_address_cache.lock().unwrap().get(&"a".into()),
+            Some(&[kvs_thread].into_iter().collect())
+        );
+        assert!(client
+            .connections
+            .established()
+            .iter()
+            .any(|(addr, _)| *addr == kvs.addr));
+    }
+
+    #[tokio::test]
+    async fn connection_hooks_fire() {
+        // a node that closes every connection right away
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+
+        let connects = Arc::new(AtomicUsize::new(0));
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let mut client = Client::new(test_config()).unwrap();
+        let counter = connects.clone();
+        client.on_connect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let counter = disconnects.clone();
+        client.on_disconnect(move |hook_addr, _| {
+            assert_eq!(hook_addr, addr);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        client.get_connection(addr).await.unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while disconnects.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_receive_tasks() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let silent = silent_addr().await;
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+        cache_key_address(&mut client, &"pending".into(), silent);
+        client.get_lww("key".into()).await.unwrap();
+
+        let mut other = client.clone();
+        let pending = tokio::spawn(async move { other.get_lww("pending".into()).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let receivers: Vec<_> = client
+            .connections
+            .established()
+            .into_iter()
+            .map(|(_, connection)| connection.receiver)
+            .collect();
+        assert_eq!(receivers.len(), 2);
+
+        let mut other = client.clone();
+        client.shutdown().await.unwrap();
+        let err = pending.await.unwrap().unwrap_err();
+        assert!(format!("{:#}", err).contains("client is shutting down"));
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !receivers.iter().all(|receiver| receiver.is_finished()) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(other.get_lww("key".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn causal_writes_merge_concurrent_versions() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut alice = Client::new(test_config()).unwrap();
+        let mut bob = Client::new(test_config()).unwrap();
+        let key: ClientKey = "causal".into();
+        cache_key_address(&mut alice, &key, kvs.addr);
+        cache_key_address(&mut bob, &key, kvs.addr);
+        let values = |payload: MultiKeyCausalPayload<SetLattice<Vec<u8>>>| {
+            let mut values: Vec<_> = payload.value.into_revealed().into_iter().collect();
+            values.sort();
+            values
+        };
+
+        // concurrent writes are both kept
+        alice.put_causal(key.clone(), b"a1".to_vec()).await.unwrap();
+        bob.put_causal(key.clone(), b"b1".to_vec()).await.unwrap();
+        let payload = bob.get_causal(key.clone()).await.unwrap();
+        assert_eq!(values(payload), [b"a1".to_vec(), b"b1".to_vec()]);
+
+        // a write after reading both versions supersedes them
+        bob.put_causal(key.clone(), b"b2".to_vec()).await.unwrap();
+        let payload = alice.get_causal(key.clone()).await.unwrap();
+        assert_eq!(values(payload), [b"b2".to_vec()]);
+        alice.put_causal(key.clone(), b"a2".to_vec()).await.unwrap();
+        let payload = bob.get_causal(key).await.unwrap();
+        assert_eq!(values(payload), [b"a2".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn put_lww_versioned_returns_stored_timestamp() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        let stored_timestamp = || match kvs.store.lock().unwrap().get(&Key::Client(key.clone())) {
+            Some(LatticeValue::Lww(lattice)) => lattice.reveal().timestamp(),
+            other => panic!("unexpected value {:?}", other),
+        };
+
+        let first = client
+            .put_lww_versioned(key.clone(), b"first".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(stored_timestamp(), first);
+        let second = client
+            .put_lww_versioned(key.clone(), b"second".to_vec())
+            .await
+            .unwrap();
+        assert!(second >= first);
+        assert_eq!(stored_timestamp(), second);
+    }
+
+    #[test]
+    fn routing_thread_selection() {
+        let config = ClientConfig {
+            routing_threads: 4,
+            ..test_config()
+        };
+        let keys: Vec<ClientKey> = vec!["key".into()];
+        let client = Client::new(ClientConfig {
+            routing_selection: RoutingSelection::HashByKey,
+            ..config.clone()
+        })
+        .unwrap();
+        let thread = client.get_routing_thread(&keys);
+        for _ in 0..10 {
+            assert_eq!(client.get_routing_thread(&keys), thread);
+        }
+
+        let client = Client::new(ClientConfig {
+            routing_selection: RoutingSelection::RoundRobin,
+            ..config
+        })
+        .unwrap();
+        let thread_ids: Vec<_> = (0..5)
+            .map(|_| client.get_routing_thread(&keys).thread_id)
+            .collect();
+        assert_eq!(thread_ids, [0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn request_ids_do_not_wrap() {
+        let mut client = Client::new(test_config()).unwrap();
+        let mut other = client.clone();
+        let ids: HashSet<_> = (0..20000)
+            .flat_map(|_| [client.gen_request_id(), other.gen_request_id()])
+            .collect();
+        assert_eq!(ids.len(), 40000);
+    }
+
+    #[tokio::test]
+    async fn raw_lattice_access() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "set".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        let set: HashSet<_> = [b"a".to_vec()].into_iter().collect();
+        client
+            .put_lattice(key.clone(), LatticeValue::Set(SetLattice::new(set.clone())))
+            .await
+            .unwrap();
+        match client.get_lattice(key.clone()).await.unwrap() {
+            LatticeValue::Set(lattice) => assert_eq!(lattice.into_revealed(), set),
+            other => panic!("unexpected lattice {:?}", other),
+        }
+
+        // merging a different lattice type is reported instead of panicking
+        let lww = LastWriterWinsLattice::from_pair(Timestamp::now(), b"value".to_vec());
+        let err = client
+            .put_lattice(key, LatticeValue::Lww(lww))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Lattice(_)));
+    }
+
+    #[tokio::test]
+    async fn put_if_absent_keeps_existing_value() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "set".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        let set = |element: &[u8]| {
+            let set: HashSet<_> = [element.to_vec()].into_iter().collect();
+            LatticeValue::Set(SetLattice::new(set))
+        };
+        assert!(client.put_if_absent(key.clone(), set(b"a")).await.unwrap());
+        assert!(!client.put_if_absent(key.clone(), set(b"b")).await.unwrap());
+        assert_eq!(client.get_lattice(key).await.unwrap(), set(b"a"));
+    }
+
+    #[tokio::test]
+    async fn get_lww_reads_single_element_set() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let single: ClientKey = "single".into();
+        let multiple: ClientKey = "multiple".into();
+        cache_key_address(&mut client, &single, kvs.addr);
+        cache_key_address(&mut client, &multiple, kvs.addr);
+
+        let set = [b"value".to_vec()].into_iter().collect();
+        client.put_set(single.clone(), set).await.unwrap();
+        assert_eq!(client.get_lww(single).await.unwrap(), b"value");
+
+        let set = [b"a".to_vec(), b"b".to_vec()].into_iter().collect();
+        client.put_set(multiple.clone(), set).await.unwrap();
+        assert!(client.get_lww(multiple).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn put_lww_acked_counts_acks() {
+        let replicas = [
+            MockKvs::start(Duration::ZERO).await,
+            MockKvs::start(Duration::ZERO).await,
+        ];
+        let mut client = Client::new(ClientConfig {
+            timeout: Duration::from_millis(200),
+            ..test_config()
+        })
+        .unwrap();
+        let key: ClientKey = "key".into();
+        for replica in &replicas {
+            cache_key_address(&mut client, &key, replica.addr);
+        }
+        cache_key_address(&mut client, &key, silent_addr().await);
+
+        let acks = client
+            .put_lww_acked(key.clone(), b"value".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(acks, 2);
+        assert!(client.response_promises.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn key_validator_rejects_locally() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let long_key: ClientKey = "a".repeat(100).into();
+        cache_key_address(&mut client, &long_key, kvs.addr);
+        client.set_key_validator(|key| {
+            if format!("{:?}", key).len() > 64 {
+                bail!("key is too long");
+            }
+            Ok(())
+        });
+
+        let err = client.get_lww(long_key).await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("key is too long"),
+            "{:#}",
+            err
+        );
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn key_does_not_exist_through_wrapping() {
+        #[derive(Debug)]
+        struct Wrapper(AnnaError);
+
+        impl std::fmt::Display for Wrapper {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("wrapped anna error")
+            }
+        }
+
+        impl std::error::Error for Wrapper {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let err = eyre::Report::new(Wrapper(AnnaError::KeyDoesNotExist))
+            .wrap_err("failed to read key")
+            .wrap_err("set_nx failed");
+        assert!(key_does_not_exist(&err));
+        let err = eyre::Report::new(Wrapper(AnnaError::Timeout)).wrap_err("failed to read key");
+        assert!(!key_does_not_exist(&err));
+    }
+
+    #[tokio::test]
+    async fn connection_info_lists_connections() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let silent = silent_addr().await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        let stuck: ClientKey = "stuck".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        cache_key_address(&mut client, &stuck, silent);
+
+        client.put_lww(key, b"value".to_vec()).await.unwrap();
+        let request = client.make_request(stuck, None).unwrap();
+        let _promise = client.send_request_to(silent, request).await.unwrap();
+
+        let mut info = client.connection_info();
+        info.sort_by_key(|info| info.addr != kvs.addr);
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].addr, kvs.addr);
+        assert_eq!(info[0].kvs_threads.len(), 1);
+        assert_eq!(info[0].in_flight, 0);
+        assert_eq!(info[1].addr, silent);
+        assert_eq!(info[1].kvs_threads.len(), 1);
+        assert_eq!(info[1].in_flight, 1);
+        assert!(info[1].last_used >= info[0].last_used);
+    }
+
+    #[test]
+    fn config_matches_input() {
+        let config = ClientConfig {
+            address_resolution: AddressResolution::Eager(vec!["key".into()]),
+            ..test_config()
+        };
+        let client = Client::new(config.clone()).unwrap();
+        assert_eq!(client.config(), &config);
+    }
+
+    #[tokio::test]
+    async fn coalesces_writes_within_window() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        client.set_write_coalescing_window(Duration::from_secs(10));
+
+        for value in ["first", "second", "third"] {
+            client
+                .put_lww(key.clone(), value.as_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 0);
+        client.flush_writes().await.unwrap();
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(client.get_lww(key).await.unwrap(), b"third");
+    }
+
+    #[tokio::test]
+    async fn get_lww_with_fallback() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("default", b"global");
+        kvs.put_lww("override", b"local");
+        let mut client = Client::new(test_config()).unwrap();
+        for key in ["default", "override", "missing", "also-missing"] {
+            cache_key_address(&mut client, &key.into(), kvs.addr);
+        }
+
+        let value = client
+            .get_lww_with_fallback("override".into(), "default".into())
+            .await;
+        assert_eq!(value.unwrap(), b"local");
+        let value = client
+            .get_lww_with_fallback("missing".into(), "default".into())
+            .await;
+        assert_eq!(value.unwrap(), b"global");
+        let err = client
+            .get_lww_with_fallback("missing".into(), "also-missing".into())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::KeyDoesNotExist));
+    }
+
+    #[tokio::test]
+    async fn put_set_get_returns_merged_set() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "set".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+
+        let set = [b"a".to_vec()].into_iter().collect();
+        client.put_set(key.clone(), set).await.unwrap();
+        let set = [b"b".to_vec(), b"c".to_vec()].into_iter().collect();
+        let merged = client.put_set_get(key, set).await.unwrap();
+        let expected: HashSet<_> = [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+            .into_iter()
+            .collect();
+        assert_eq!(merged, expected);
+    }
+
+    #[tokio::test]
+    async fn concurrent_first_use_opens_one_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                streams.push(stream);
+            }
+        });
+
+        let client = Client::new(test_config()).unwrap();
+        let attempts: Vec<_> = (0..16)
+            .map(|_| {
+                let connections = client.connections.clone();
+                let this = ThisClient::from(&client);
+                tokio::spawn(async move { connections.get(this, addr).await.map(|_| ()) })
+            })
+            .collect();
+        for attempt in attempts {
+            attempt.await.unwrap().unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(client.connection_info().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn requests_time_out() {
+        let silent = silent_addr().await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: silent.port(),
+            timeout: Duration::from_millis(100),
+            ..test_config()
+        })
+        .unwrap();
+
+        // the routing node never answers the address request
+        let start = Instant::now();
+        let err = client.get_lww("unknown".into()).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("timed out"), "{:#}", err);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(client.address_response_promises.lock().await.is_empty());
+
+        // the KVS node never answers the request
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, silent_addr().await);
+        let start = Instant::now();
+        let err = client.get_lww(key).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("timed out"), "{:#}", err);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(client.response_promises.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn repeated_timeouts_refresh_key_address() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            timeout: Duration::from_millis(100),
+            address_refresh_timeouts: Some(2),
+            ..test_config()
+        })
+        .unwrap();
+        // the cached node went dark and the key moved without the client noticing
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, silent_addr().await);
+
+        for cached_after in [true, false] {
+            let err = client.get_lww(key.clone()).await.unwrap_err();
+            assert!(matches!(err, ClientError::Timeout(_)), "{}", err);
+            let cached = client.key_address_cache.lock().unwrap().contains_key(&key);
+            assert_eq!(cached, cached_after);
+        }
+        assert_eq!(client.get_lww(key.clone()).await.unwrap(), b"value");
+        assert!(client.key_timeouts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconnects_to_down_node_with_backoff() {
+        let mut client = Client::new(test_config()).unwrap();
+        client.set_reconnect_backoff(ReconnectBackoff {
+            base_delay: Duration::from_millis(50),
+            jitter: 0.0,
+            ..Default::default()
+        });
+        let failures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let failures_clone = failures.clone();
+        client.on_connect_failure(move |_, failure| {
+            let mut failures = failures_clone.lock().unwrap();
+            failures.push((Instant::now(), failure.failures, failure.retry_after));
+        });
+        let key: ClientKey = "key".into();
+        let dead = dead_addr().await;
+
+        // the node stays down while the client keeps sending requests to it
+        let start = Instant::now();
+        let mut requests = 0;
+        while start.elapsed() < Duration::from_millis(700) {
+            cache_key_address(&mut client, &key, dead);
+            assert!(client.get_lww(key.clone()).await.is_err());
+            requests += 1;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let failures = failures.lock().unwrap();
+        assert!(failures.len() >= 3 && failures.len() < 6, "{:?}", failures);
+        assert!(requests > 2 * failures.len(), "{}", requests);
+        for (i, &(_, count, retry_after)) in failures.iter().enumerate() {
+            assert_eq!(count as usize, i + 1);
+            assert_eq!(retry_after, Duration::from_millis(50 << i));
+        }
+        // each attempt waits for the backoff of the previous failure
+        for pair in failures.windows(2) {
+            let ((previous, _, retry_after), (next, _, _)) = (pair[0], pair[1]);
+            assert!(next - previous >= retry_after);
+        }
+    }
+
+    #[tokio::test]
+    async fn late_responses_are_counted_as_orphans() {
+        let kvs = MockKvs::start(Duration::from_millis(100)).await;
+        kvs.put_lww("key", b"value");
+        let mut client = Client::new(ClientConfig {
+            timeout: Duration::from_millis(20),
+            ..test_config()
+        })
+        .unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+
+        assert!(client.get_lww("key".into()).await.is_err());
+        assert_eq!(client.metrics_snapshot().orphan_responses, 0);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while client.metrics_snapshot().orphan_responses == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_connection_closed() {
+        // a proxy to a KVS that closes the first connection right away
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    continue;
+                }
+                let mut upstream = TcpStream::connect(kvs.addr).await.unwrap();
+                tokio::spawn(async move {
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await;
+                });
+            }
+        });
+
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "key".into();
+        cache_key_address(&mut client, &key, addr);
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let counter = disconnects.clone();
+        client.on_disconnect(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        client.get_connection(addr).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while disconnects.load(Ordering::SeqCst) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        client
+            .put_lww(key.clone(), b"value".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(client.get_lww(key).await.unwrap(), b"value");
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn routing_error_invalidates_cache() {
+        let port = mock_routing(Vec::new()).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+        // the key's KVS thread is known, but its tcp address is not
+        let key: ClientKey = "key".into();
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        client
+            .key_address_cache
+            .lock()
+            .unwrap()
+            .insert(key.clone(), kvs_thread);
+
+        let err = client.get_lww(key.clone()).await.unwrap_err();
+        assert!(matches!(err, ClientError::NoReplicas), "{}", err);
+        assert!(!client.key_address_cache.lock().unwrap().contains_key(&key));
+    }
+
+    #[tokio::test]
+    async fn reports_address_cache_size_and_handles_invalidation() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+        let key: ClientKey = "key".into();
+
+        // the first request is made before the address of the key is known
+        client.get_lww(key.clone()).await.unwrap();
+        assert!(kvs.address_cache_size.lock().unwrap().is_empty());
+        client.get_lww(key.clone()).await.unwrap();
+        assert_eq!(
+            *kvs.address_cache_size.lock().unwrap(),
+            HashMap::from([(key.clone(), 1)])
+        );
+
+        kvs.invalidate.store(true, Ordering::SeqCst);
+        client.get_lww(key.clone()).await.unwrap();
+        assert!(!client.key_address_cache.lock().unwrap().contains_key(&key));
+
+        // the next request queries the routing tier again
+        kvs.invalidate.store(false, Ordering::SeqCst);
+        client.get_lww(key.clone()).await.unwrap();
+        assert!(client.key_address_cache.lock().unwrap().contains_key(&key));
+    }
+
+    #[tokio::test]
+    async fn address_cache_evicts_least_recently_used_key() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        for key in ["a", "b", "c"] {
+            kvs.put_lww(key, b"value");
+        }
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            address_cache_capacity: Some(2),
+            ..test_config()
+        })
+        .unwrap();
+
+        for key in ["a", "b", "a", "c"] {
+            client.get_lww(key.into()).await.unwrap();
+        }
+        let cached = |client: &Client, key: &str| {
+            client
+                .key_address_cache
+                .lock()
+                .unwrap()
+                .contains_key(&key.into())
+        };
+        assert!(cached(&client, "a"));
+        assert!(!cached(&client, "b"));
+        assert!(cached(&client, "c"));
+
+        // `b` is queried from the routing tier again, evicting `a`
+        client.get_lww("b".into()).await.unwrap();
+        assert!(!cached(&client, "a"));
+        assert!(cached(&client, "b"));
+    }
+
+    #[tokio::test]
+    async fn get_lww_many_batches_address_queries() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("a", b"value a");
+        kvs.put_lww("b", b"value b");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+        let mut values = client
+            .get_lww_many(vec!["a".into(), "b".into(), "c".into(), "a".into()])
+            .await
+            .unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values.remove(&"a".into()).unwrap().unwrap(), b"value a");
+        assert_eq!(values.remove(&"b".into()).unwrap().unwrap(), b"value b");
+        assert!(matches!(
+            values.remove(&"c".into()).unwrap(),
+            Err(ClientError::KeyDoesNotExist)
+        ));
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 3);
+        // one address request and one request per distinct key
+        assert_eq!(client.next_request_id.load(Ordering::SeqCst), 1 + 4);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_live_routing_node() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        // the routing node only listens on 127.0.0.1
+        let dead_ip: IpAddr = "127.0.0.2".parse().unwrap();
+        let mut client = Client::new(ClientConfig {
+            routing_ip: dead_ip,
+            fallback_routing_ips: vec!["127.0.0.1".parse().unwrap()],
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+
+        assert_eq!(client.get_lww("key".into()).await.unwrap(), b"value");
+        assert!(client.failed_routers.lock().unwrap().contains_key(&dead_ip));
+        // the failed routing node is tried last during its cooldown
+        let addrs = client.get_routing_tcp_addresses(&[]);
+        assert_eq!(
+            addrs[0],
+            SocketAddr::new("127.0.0.1".parse().unwrap(), port)
+        );
+        assert_eq!(addrs[1], SocketAddr::new(dead_ip, port));
+    }
+
+    #[tokio::test]
+    async fn get_lww_traced_reports_address_cache_status() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+
+        let (value, status) = client.get_lww_traced("key".into()).await.unwrap();
+        assert_eq!(value, b"value");
+        assert_eq!(status, AddressCacheStatus::Miss);
+        let (value, status) = client.get_lww_traced("key".into()).await.unwrap();
+        assert_eq!(value, b"value");
+        assert_eq!(status, AddressCacheStatus::Hit);
+
+        // a cached thread without a cached tcp address is a miss
+        client.kvs_tcp_address_cache.lock().unwrap().clear();
+        let (value, status) = client.get_lww_traced("key".into()).await.unwrap();
+        assert_eq!(value, b"value");
+        assert_eq!(status, AddressCacheStatus::Miss);
+    }
+
+    #[tokio::test]
+    async fn get_lww_many_classifies_errors_per_key() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("present", b"value");
+        let mut client = Client::new(ClientConfig {
+            timeout: Duration::from_millis(200),
+            ..test_config()
+        })
+        .unwrap();
+        cache_key_address(&mut client, &"present".into(), kvs.addr);
+        cache_key_address(&mut client, &"missing".into(), kvs.addr);
+        let slow = silent_addr().await;
+        cache_key_address(&mut client, &"slow".into(), slow);
+
+        let mut values = client
+            .get_lww_many(vec!["present".into(), "missing".into(), "slow".into()])
+            .await
+            .unwrap();
+        assert_eq!(values.remove(&"present".into()).unwrap().unwrap(), b"value");
+        assert!(matches!(
+            values.remove(&"missing".into()).unwrap(),
+            Err(ClientError::KeyDoesNotExist)
+        ));
+        // the error names the key and the address of the request
+        assert!(matches!(
+            values.remove(&"slow".into()).unwrap(),
+            Err(ClientError::Timeout(message))
+                if message.contains("\"slow\"") && message.contains(&slow.to_string())
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_lww_many_mixes_consistency_levels() {
+        let stale = MockKvs::start(Duration::ZERO).await;
+        stale.put_lww("critical", b"old");
+        let fresh = MockKvs::start(Duration::ZERO).await;
+        fresh.put_lww("critical", b"new");
+        let plain = MockKvs::start(Duration::ZERO).await;
+        plain.put_lww("plain", b"value");
+        let timeout = Duration::from_secs(5);
+        let mut client = Client::new(ClientConfig {
+            timeout,
+            ..test_config()
+        })
+        .unwrap();
+        let critical: ClientKey = "critical".into();
+        cache_key_address(&mut client, &critical, stale.addr);
+        cache_key_address(&mut client, &critical, fresh.addr);
+        cache_key_address(&mut client, &critical, silent_addr().await);
+        cache_key_address(&mut client, &"plain".into(), plain.addr);
+
+        let start = Instant::now();
+        let mut values = client
+            .get_lww_many_with_consistency(vec![
+                (critical.clone(), ConsistencyLevel::Eventual),
+                ("plain".into(), ConsistencyLevel::Eventual),
+                (critical.clone(), ConsistencyLevel::Quorum),
+            ])
+            .await
+            .unwrap();
+        // two of the three replicas form a quorum, the silent one is not waited for
+        assert!(start.elapsed() < timeout);
+        assert_eq!(values.remove(&critical).unwrap().unwrap(), b"new");
+        assert_eq!(values.remove(&"plain".into()).unwrap().unwrap(), b"value");
+        assert_eq!(stale.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(fresh.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(plain.requests.load(Ordering::SeqCst), 1);
+        assert!(client.response_promises.lock().await.is_empty());
+    }
+
+    /// A tracing subscriber that records the fields and parents of all spans, and the
+    /// levels and messages of all events.
+    #[derive(Default)]
+    pub(crate) struct SpanCapture {
+        spans: std::sync::Mutex<Vec<CapturedSpan>>,
+        stack: std::sync::Mutex<Vec<tracing::span::Id>>,
+        pub events: std::sync::Mutex<Vec<(tracing::Level, String)>>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct CapturedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    impl tracing::field::Visit for CapturedSpan {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.fields.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl tracing::Subscriber for SpanCapture {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut spans = self.spans.lock().unwrap();
+            let parent = attrs
+                .parent()
+                .cloned()
+                .or_else(|| self.stack.lock().unwrap().last().cloned())
+                .map(|id| spans[id.into_u64() as usize - 1].name);
+            let mut span = CapturedSpan {
+                name: attrs.metadata().name(),
+                parent,
+                fields: HashMap::new(),
+            };
+            attrs.record(&mut span);
+            spans.push(span);
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            values.record(&mut self.spans.lock().unwrap()[id.into_u64() as usize - 1]);
+        }
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut captured = CapturedSpan {
+                name: event.metadata().name(),
+                parent: None,
+                fields: HashMap::new(),
+            };
+            event.record(&mut captured);
+            let message = captured.fields.remove("message").unwrap_or_default();
+            let level = *event.metadata().level();
+            self.events.lock().unwrap().push((level, message));
+        }
+
+        fn enter(&self, id: &tracing::span::Id) {
+            self.stack.lock().unwrap().push(id.clone());
+        }
+
+        fn exit(&self, _: &tracing::span::Id) {
+            self.stack.lock().unwrap().pop();
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_traced() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+
+        let capture = Arc::new(SpanCapture::default());
+        let _guard = tracing::dispatcher::set_default(&tracing::Dispatch::from(capture.clone()));
+        client.get_lww("key".into()).await.unwrap();
+
+        let spans = capture.spans.lock().unwrap().clone();
+        let request = spans
+            .iter()
+            .find(|span| span.name == "send_request")
+            .expect("no send_request span");
+        assert!(request.fields.contains_key("request_id"));
+        assert_eq!(
+            request.fields["key"],
+            format!("{:?}", ClientKey::from("key"))
+        );
+        assert!(request.fields["kvs_thread"].contains(&format!("kvs-{}", kvs.addr)));
+        let resolve = spans
+            .iter()
+            .find(|span| span.name == "resolve_address")
+            .expect("no resolve_address span");
+        assert_eq!(resolve.parent, Some("send_request"));
+    }
+
+    #[tokio::test]
+    async fn cloned_handles_share_state() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        for i in 0..10 {
+            cache_key_address(&mut client, &format!("key-{}", i).into(), kvs.addr);
+        }
+
+        let tasks: Vec<_> = (0..2)
+            .map(|handle| {
+                let mut client = client.clone();
+                tokio::spawn(async move {
+                    let mut request_ids = Vec::new();
+                    for i in 0..10 {
+                        let key: ClientKey = format!("key-{}", i).into();
+                        let value = format!("{}-{}", handle, i).into_bytes();
+                        client.put_lww(key.clone(), value).await.unwrap();
+                        client.get_lww(key.clone()).await.unwrap();
+                        request_ids.push(client.make_request(key, None).unwrap().request_id);
+                    }
+                    request_ids
+                })
+            })
+            .collect();
+        let mut request_ids = HashSet::new();
+        for task in tasks {
+            for request_id in task.await.unwrap() {
+                assert!(request_ids.insert(request_id));
+            }
+        }
+
+        assert_eq!(request_ids.len(), 20);
+        assert_eq!(client.next_request_id.load(Ordering::SeqCst), 1 + 60);
+        assert_eq!(client.connection_info().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn exists() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("present", b"value");
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"present".into(), kvs.addr);
+        cache_key_address(&mut client, &"missing".into(), kvs.addr);
+        cache_key_address(&mut client, &"unreachable".into(), dead_addr().await);
+
+        assert!(client.exists("present".into()).await.unwrap());
+        assert!(!client.exists("missing".into()).await.unwrap());
+        assert!(client.exists("unreachable".into()).await.is_err());
     }
 }
@@ -0,0 +1,133 @@
+//! Provides [`NamespacedClient`] for isolating the keys of applications sharing a cluster.
+
+use std::collections::{HashMap, HashSet};
+
+use anna_api::ClientKey;
+
+use super::{Client, ClientError};
+
+/// Client wrapper that prepends a fixed prefix to all keys.
+///
+/// Applications using different prefixes on the same cluster can't access each other's
+/// keys through their namespaced clients. Keys in results are reported without the
+/// prefix.
+pub struct NamespacedClient {
+    client: Client,
+    prefix: String,
+}
+
+impl Client {
+    /// Wraps this client in a [`NamespacedClient`] that prepends `prefix` to all keys.
+    pub fn with_prefix(self, prefix: impl Into<String>) -> NamespacedClient {
+        NamespacedClient {
+            client: self,
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl NamespacedClient {
+    /// Returns the prefix of this namespace.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the wrapped client, which accesses keys without the prefix.
+    pub fn inner(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Returns the key under which the given key of this namespace is stored.
+    pub fn namespaced_key(&self, key: &ClientKey) -> ClientKey {
+        format!("{}{}", self.prefix, &**key).into()
+    }
+
+    /// Try to put a *last writer wins* value with the given key.
+    pub async fn put_lww(&mut self, key: ClientKey, value: Vec<u8>) -> Result<(), ClientError> {
+        let key = self.namespaced_key(&key);
+        self.client.put_lww(key, value).await
+    }
+
+    /// Try to get a *last writer wins* value with the given key.
+    pub async fn get_lww(&mut self, key: ClientKey) -> Result<Vec<u8>, ClientError> {
+        let key = self.namespaced_key(&key);
+        self.client.get_lww(key).await
+    }
+
+    /// Try to get the *last writer wins* values of multiple keys, see
+    /// [`Client::get_lww_many`].
+    pub async fn get_lww_many(
+        &mut self,
+        keys: Vec<ClientKey>,
+    ) -> Result<HashMap<ClientKey, Result<Vec<u8>, ClientError>>, ClientError> {
+        let keys = keys.iter().map(|key| self.namespaced_key(key)).collect();
+        let values = self.client.get_lww_many(keys).await?;
+        Ok(values
+            .into_iter()
+            .map(|(key, value)| (self.strip_prefix(&key), value))
+            .collect())
+    }
+
+    /// Returns whether the given key exists.
+    pub async fn exists(&mut self, key: ClientKey) -> Result<bool, ClientError> {
+        let key = self.namespaced_key(&key);
+        self.client.exists(key).await
+    }
+
+    /// Try to put a set value with the given key.
+    pub async fn put_set(
+        &mut self,
+        key: ClientKey,
+        set: HashSet<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let key = self.namespaced_key(&key);
+        self.client.put_set(key, set).await
+    }
+
+    /// Try to get a set value with the given key.
+    pub async fn get_set(&mut self, key: ClientKey) -> Result<HashSet<Vec<u8>>, ClientError> {
+        let key = self.namespaced_key(&key);
+        self.client.get_set(key).await
+    }
+
+    fn strip_prefix(&self, key: &ClientKey) -> ClientKey {
+        match key.strip_prefix(self.prefix.as_str()) {
+            Some(key) => key.into(),
+            None => key.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, test_config, MockKvs},
+        Key,
+    };
+
+    #[tokio::test]
+    async fn keys_are_prefixed() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"app1:key".into(), kvs.addr);
+        let mut client = client.with_prefix("app1:");
+
+        client
+            .put_lww("key".into(), b"value".to_vec())
+            .await
+            .unwrap();
+        assert!(kvs
+            .store
+            .lock()
+            .unwrap()
+            .get(&Key::Client("app1:key".into()))
+            .is_some());
+        assert_eq!(client.get_lww("key".into()).await.unwrap(), b"value");
+
+        let values = client.get_lww_many(vec!["key".into()]).await.unwrap();
+        assert_eq!(values.keys().collect::<Vec<_>>(), vec![&"key".into()]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Provides [`RequestOptions`] for overriding the request settings of a [`Client`] per call.
+
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use super::{Client, RetryPolicy};
+
+/// Settings that override the defaults of a [`Client`] for some calls, see
+/// [`Client::with_options`].
+///
+/// Settings that are `None` fall back to the client's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// The timeout of each request, instead of the configured timeout.
+    pub timeout: Option<Duration>,
+    /// The retry policy, instead of the one set with [`Client::set_retry_policy`].
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+/// A [`Client`] with overridden request settings, created by [`Client::with_options`].
+///
+/// Dereferences to the client and restores its defaults when dropped.
+pub struct WithOptions<'a> {
+    client: &'a mut Client,
+    timeout: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl Client {
+    /// Overrides the request settings of this client until the returned guard is dropped.
+    ///
+    /// This is meant for single calls that need different settings than the rest, e.g. a
+    /// bulk operation that needs a longer timeout:
+    ///
+    /// ```no_run
+    /// # async fn example(client: &mut wasmedge_anna_client::Client) -> eyre::Result<()> {
+    /// use std::time::Duration;
+    /// use wasmedge_anna_client::RequestOptions;
+    ///
+    /// let options = RequestOptions {
+    ///     timeout: Some(Duration::from_secs(60)),
+    ///     ..Default::default()
+    /// };
+    /// let value = client.with_options(options).get_lww("key".into()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options(&mut self, options: RequestOptions) -> WithOptions<'_> {
+        let timeout = self.config.timeout;
+        let retry_policy = self.retry_policy.clone();
+        if let Some(timeout) = options.timeout {
+            self.config.timeout = timeout;
+        }
+        if let Some(retry_policy) = options.retry_policy {
+            self.retry_policy = retry_policy;
+        }
+        WithOptions {
+            client: self,
+            timeout,
+            retry_policy,
+        }
+    }
+}
+
+impl Deref for WithOptions<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl DerefMut for WithOptions<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client
+    }
+}
+
+impl Drop for WithOptions<'_> {
+    fn drop(&mut self) {
+        self.client.config.timeout = self.timeout;
+        self.client.retry_policy = std::mem::take(&mut self.retry_policy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, silent_addr, test_config},
+        ClientConfig,
+    };
+
+    #[tokio::test]
+    async fn per_call_timeout_overrides_default() {
+        let mut client = Client::new(ClientConfig {
+            timeout: Duration::from_secs(10),
+            ..test_config()
+        })
+        .unwrap();
+        cache_key_address(&mut client, &"key".into(), silent_addr().await);
+
+        let options = RequestOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let result = client.with_options(options).get_lww("key".into()).await;
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(client.config().timeout, Duration::from_secs(10));
+    }
+}
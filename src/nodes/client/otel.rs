@@ -0,0 +1,201 @@
+//! Export of client metrics and spans through [OpenTelemetry](https://opentelemetry.io/),
+//! enabled by the `opentelemetry` feature.
+//!
+//! The client instruments its requests with [`tracing`] spans: a `send_request` span per
+//! attempt, with the request ID, key, attempt number, and KVS thread, and a child
+//! `resolve_address` span for the address lookup of the key. Retries of a request show up
+//! as `send_request` spans with increasing attempt numbers. To export the spans, add a
+//! [`tracing-opentelemetry`](https://docs.rs/tracing-opentelemetry) layer to the
+//! subscriber of the application. The metrics of [`Client::metrics_snapshot`] are
+//! exported by [`Client::register_otel_metrics`].
+//!
+//! For example, to send both to an OTLP collector with the `opentelemetry-otlp` crate:
+//!
+//! ```ignore
+//! use opentelemetry::{metrics::MeterProvider, trace::TracerProvider};
+//! use tracing_subscriber::prelude::*;
+//!
+//! let span_exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic().build()?;
+//! let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+//!     .with_batch_exporter(span_exporter)
+//!     .build();
+//! tracing_subscriber::registry()
+//!     .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("anna")))
+//!     .init();
+//!
+//! let metric_exporter = opentelemetry_otlp::MetricExporter::builder().with_tonic().build()?;
+//! let meter_provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+//!     .with_periodic_exporter(metric_exporter)
+//!     .build();
+//! client.register_otel_metrics(&meter_provider.meter("anna"));
+//! ```
+
+use opentelemetry::{metrics::Meter, KeyValue};
+
+use super::Client;
+
+impl Client {
+    /// Registers observable instruments on the given meter that report the metrics of
+    /// this client, see [`ClientMetrics`][super::ClientMetrics].
+    ///
+    /// All handles of a client share the same metrics, so this is only needed once per
+    /// client. The instruments are:
+    ///
+    /// - `anna.client.requests`: the number of requests sent to KVS threads,
+    /// - `anna.client.errors`: the number of errors, with the error kind as `kind`
+    ///   attribute,
+    /// - `anna.client.address_lookups`: the number of key address lookups, with `hit` set
+    ///   to whether they were served from the cache,
+    /// - `anna.client.request.duration` and `anna.client.request.count`: the summed
+    ///   latency of the received responses in seconds and their number,
+    /// - `anna.client.orphan_responses`: the number of responses without a waiting
+    ///   request.
+    pub fn register_otel_metrics(&self, meter: &Meter) {
+        let metrics = self.metrics.clone();
+        meter
+            .u64_observable_counter("anna.client.requests")
+            .with_description("Requests sent to KVS threads")
+            .with_callback(move |observer| observer.observe(metrics.snapshot().requests, &[]))
+            .build();
+
+        let metrics = self.metrics.clone();
+        meter
+            .u64_observable_counter("anna.client.errors")
+            .with_description("Failed requests and errors reported for keys")
+            .with_callback(move |observer| {
+                let errors = metrics.snapshot().errors;
+                let counts = [
+                    ("key_does_not_exist", errors.key_does_not_exist),
+                    ("timeout", errors.timeout),
+                    ("no_replicas", errors.no_replicas),
+                    ("transport", errors.transport),
+                    ("protocol", errors.protocol),
+                    ("lattice", errors.lattice),
+                ];
+                for (kind, count) in counts {
+                    observer.observe(count, &[KeyValue::new("kind", kind)]);
+                }
+            })
+            .build();
+
+        let metrics = self.metrics.clone();
+        meter
+            .u64_observable_counter("anna.client.address_lookups")
+            .with_description("Key address lookups")
+            .with_callback(move |observer| {
+                let snapshot = metrics.snapshot();
+                observer.observe(snapshot.address_cache_hits, &[KeyValue::new("hit", true)]);
+                observer.observe(
+                    snapshot.address_cache_misses,
+                    &[KeyValue::new("hit", false)],
+                );
+            })
+            .build();
+
+        let metrics = self.metrics.clone();
+        meter
+            .f64_observable_counter("anna.client.request.duration")
+            .with_description("Summed time from the creation of requests to their responses")
+            .with_unit("s")
+            .with_callback(move |observer| {
+                observer.observe(metrics.snapshot().latency.sum.as_secs_f64(), &[])
+            })
+            .build();
+
+        let metrics = self.metrics.clone();
+        meter
+            .u64_observable_counter("anna.client.request.count")
+            .with_description("Responses whose latency was recorded")
+            .with_callback(move |observer| observer.observe(metrics.snapshot().latency.count, &[]))
+            .build();
+
+        let metrics = self.metrics.clone();
+        meter
+            .u64_observable_counter("anna.client.orphan_responses")
+            .with_description("Responses that arrived without a waiting request")
+            .with_callback(move |observer| {
+                observer.observe(metrics.snapshot().orphan_responses, &[])
+            })
+            .build();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opentelemetry::{metrics::MeterProvider, trace::TracerProvider};
+    use opentelemetry_sdk::{
+        metrics::{
+            data::{AggregatedMetrics, MetricData},
+            InMemoryMetricExporter, SdkMeterProvider,
+        },
+        trace::{InMemorySpanExporter, SdkTracerProvider},
+    };
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn exports_spans_of_single_operation() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let guard = tracing::subscriber::set_default(subscriber);
+        client.get_lww("key".into()).await.unwrap();
+        drop(guard);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let request = spans
+            .iter()
+            .find(|span| span.name == "send_request")
+            .expect("no send_request span");
+        let resolve = spans
+            .iter()
+            .find(|span| span.name == "resolve_address")
+            .expect("no resolve_address span");
+        assert_eq!(resolve.parent_span_id, request.span_context.span_id());
+        assert!(request.attributes.iter().any(
+            |attribute| attribute.key.as_str() == "attempt" && attribute.value.as_str() == "1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn exports_metrics() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+        client.get_lww("key".into()).await.unwrap();
+
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter.clone())
+            .build();
+        client.register_otel_metrics(&provider.meter("test"));
+        provider.force_flush().unwrap();
+
+        let exported = exporter.get_finished_metrics().unwrap();
+        let requests = exported
+            .iter()
+            .flat_map(|resource| resource.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .find(|metric| metric.name() == "anna.client.requests")
+            .expect("no requests metric");
+        let sum = match requests.data() {
+            AggregatedMetrics::U64(MetricData::Sum(sum)) => sum,
+            other => panic!("unexpected metric data {:?}", other),
+        };
+        let values: Vec<u64> = sum.data_points().map(|point| point.value()).collect();
+        assert_eq!(values, vec![1]);
+    }
+}
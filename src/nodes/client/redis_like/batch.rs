@@ -0,0 +1,126 @@
+//! Provides [`Command`] and [`RedisValue`] for executing heterogeneous command batches.
+
+use anna_api::ClientKey;
+
+use super::Connection;
+
+/// A command that can be executed as part of a batch through [`Connection::exec_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// GET key
+    Get(ClientKey),
+    /// SET key value
+    Set(ClientKey, Vec<u8>),
+    /// SETNX key value
+    SetNx(ClientKey, Vec<u8>),
+    /// SETBIT key offset value
+    SetBit(ClientKey, usize, bool),
+    /// GETBIT key offset
+    GetBit(ClientKey, usize),
+    /// BITCOUNT key
+    BitCount(ClientKey),
+}
+
+/// The result of a [`Command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedisValue {
+    /// The key does not exist.
+    Nil,
+    /// The command succeeded without returning a value.
+    Okay,
+    /// A stored value.
+    Data(Vec<u8>),
+    /// A single bit.
+    Bool(bool),
+    /// A count.
+    Int(usize),
+}
+
+impl Command {
+    /// Returns the key the command operates on.
+    pub(super) fn key(&self) -> &ClientKey {
+        match self {
+            Command::Get(key)
+            | Command::Set(key, _)
+            | Command::SetNx(key, _)
+            | Command::SetBit(key, _, _)
+            | Command::GetBit(key, _)
+            | Command::BitCount(key) => key,
+        }
+    }
+}
+
+impl Connection {
+    /// Executes the given commands in order and returns their results in the same order.
+    ///
+    /// Execution stops at the first command that fails. A `GET` of a missing key is not
+    /// a failure, it yields [`RedisValue::Nil`].
+    pub async fn exec_batch(&mut self, commands: Vec<Command>) -> eyre::Result<Vec<RedisValue>> {
+        let mut results = Vec::with_capacity(commands.len());
+        for command in commands {
+            let result = match command {
+                Command::Get(key) => match self.get_bytes(key).await? {
+                    Some(value) => RedisValue::Data(value),
+                    None => RedisValue::Nil,
+                },
+                Command::Set(key, value) => {
+                    self.set(key, value).await?;
+                    RedisValue::Okay
+                }
+                Command::SetNx(key, value) => {
+                    self.set_nx(key, value).await?;
+                    RedisValue::Okay
+                }
+                Command::SetBit(key, offset, value) => {
+                    RedisValue::Bool(self.setbit(key, offset, value).await?)
+                }
+                Command::GetBit(key, offset) => RedisValue::Bool(self.getbit(key, offset).await?),
+                Command::BitCount(key) => RedisValue::Int(self.bitcount(key).await?),
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, test_config, MockKvs},
+        redis_like::Client,
+    };
+
+    #[tokio::test]
+    async fn batch_results_in_order() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["a", "bits", "missing"] {
+            cache_key_address(&mut con.client, &key.into(), kvs.addr);
+        }
+
+        let results = con
+            .exec_batch(vec![
+                Command::Set("a".into(), b"value".to_vec()),
+                Command::Get("a".into()),
+                Command::SetBit("bits".into(), 9, true),
+                Command::BitCount("bits".into()),
+                Command::Get("missing".into()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![
+                RedisValue::Okay,
+                RedisValue::Data(b"value".to_vec()),
+                RedisValue::Bool(false),
+                RedisValue::Int(1),
+                RedisValue::Nil,
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Bit-level helpers for the `SETBIT`, `GETBIT` and `BITCOUNT` commands.
+//!
+//! Like Redis, bit offsets address the most significant bit of the first byte as bit 0.
+
+/// Returns the bit at the given offset, treating bits beyond the end of `bytes` as 0.
+pub fn get_bit(bytes: &[u8], offset: usize) -> bool {
+    match bytes.get(offset / 8) {
+        Some(byte) => byte & mask(offset) != 0,
+        None => false,
+    }
+}
+
+/// Sets the bit at the given offset and returns its previous value.
+///
+/// The value is zero-filled as needed when `offset` lies beyond its current end.
+pub fn set_bit(bytes: &mut Vec<u8>, offset: usize, value: bool) -> bool {
+    let index = offset / 8;
+    if bytes.len() <= index {
+        bytes.resize(index + 1, 0);
+    }
+    let old = bytes[index] & mask(offset) != 0;
+    if value {
+        bytes[index] |= mask(offset);
+    } else {
+        bytes[index] &= !mask(offset);
+    }
+    old
+}
+
+/// Counts the bits set to 1.
+pub fn count_bits(bytes: &[u8]) -> usize {
+    bytes.iter().map(|byte| byte.count_ones() as usize).sum()
+}
+
+fn mask(offset: usize) -> u8 {
+    0b1000_0000 >> (offset % 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_across_bytes() {
+        let mut bytes = Vec::new();
+        assert!(!set_bit(&mut bytes, 7, true));
+        assert!(!set_bit(&mut bytes, 8, true));
+        assert!(!set_bit(&mut bytes, 23, true));
+        assert_eq!(bytes, vec![0b0000_0001, 0b1000_0000, 0b0000_0001]);
+
+        assert!(get_bit(&bytes, 7));
+        assert!(get_bit(&bytes, 8));
+        assert!(!get_bit(&bytes, 9));
+        assert!(get_bit(&bytes, 23));
+        assert!(!get_bit(&bytes, 1000));
+        assert_eq!(count_bits(&bytes), 3);
+
+        assert!(set_bit(&mut bytes, 8, false));
+        assert!(!get_bit(&bytes, 8));
+        assert_eq!(count_bits(&bytes), 2);
+    }
+}
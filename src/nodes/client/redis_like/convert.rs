@@ -1,6 +1,14 @@
-use eyre::ContextCompat;
+//! Conversions between Rust values and the bytes stored in Anna.
+//!
+//! Numbers are encoded in big-endian byte order. `usize` and `isize` are always
+//! encoded as 8 bytes, so that values can be shared between 64-bit hosts and 32-bit
+//! targets such as wasm32.
 
+use eyre::{eyre, ContextCompat};
+
+/// Types that can be encoded as the bytes of an Anna value.
 pub trait ToAnnaValue: Sized {
+    /// Encodes the value.
     fn to_anna_value(&self) -> Vec<u8>;
 }
 
@@ -22,6 +30,12 @@ impl ToAnnaValue for &str {
     }
 }
 
+impl ToAnnaValue for bool {
+    fn to_anna_value(&self) -> Vec<u8> {
+        vec![u8::from(*self)]
+    }
+}
+
 impl ToAnnaValue for u8 {
     fn to_anna_value(&self) -> Vec<u8> {
         vec![*self]
@@ -70,19 +84,51 @@ impl ToAnnaValue for i64 {
     }
 }
 
-impl ToAnnaValue for usize {
+impl ToAnnaValue for u128 {
     fn to_anna_value(&self) -> Vec<u8> {
         self.to_be_bytes().to_vec()
     }
 }
 
+impl ToAnnaValue for i128 {
+    fn to_anna_value(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
+impl ToAnnaValue for usize {
+    fn to_anna_value(&self) -> Vec<u8> {
+        (*self as u64).to_be_bytes().to_vec()
+    }
+}
+
 impl ToAnnaValue for isize {
+    fn to_anna_value(&self) -> Vec<u8> {
+        (*self as i64).to_be_bytes().to_vec()
+    }
+}
+
+impl ToAnnaValue for f32 {
     fn to_anna_value(&self) -> Vec<u8> {
         self.to_be_bytes().to_vec()
     }
 }
 
+impl ToAnnaValue for f64 {
+    fn to_anna_value(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
+impl ToAnnaValue for char {
+    fn to_anna_value(&self) -> Vec<u8> {
+        u32::from(*self).to_be_bytes().to_vec()
+    }
+}
+
+/// Types that can be decoded from the bytes of an Anna value.
 pub trait FromAnnaValue: Sized {
+    /// Decodes the value.
     fn from_anna_value(value: &[u8]) -> eyre::Result<Self>;
 }
 
@@ -98,6 +144,15 @@ impl FromAnnaValue for String {
     }
 }
 
+impl FromAnnaValue for bool {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        Ok(*value
+            .first()
+            .context("cannot convert empty value to bool")?
+            != 0)
+    }
+}
+
 impl FromAnnaValue for u8 {
     fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
         Ok(*value.get(0).context("cannot convert empty value to u8")?)
@@ -146,14 +201,130 @@ impl FromAnnaValue for i64 {
     }
 }
 
+impl FromAnnaValue for u128 {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        Ok(u128::from_be_bytes(value.try_into()?))
+    }
+}
+
+impl FromAnnaValue for i128 {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        Ok(i128::from_be_bytes(value.try_into()?))
+    }
+}
+
 impl FromAnnaValue for usize {
     fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
-        Ok(usize::from_be_bytes(value.try_into()?))
+        decode_unsigned(value, "usize")
     }
 }
 
 impl FromAnnaValue for isize {
     fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
-        Ok(isize::from_be_bytes(value.try_into()?))
+        decode_signed(value, "isize")
+    }
+}
+
+impl FromAnnaValue for f32 {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        Ok(f32::from_be_bytes(value.try_into()?))
+    }
+}
+
+impl FromAnnaValue for f64 {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        Ok(f64::from_be_bytes(value.try_into()?))
+    }
+}
+
+impl FromAnnaValue for char {
+    fn from_anna_value(value: &[u8]) -> eyre::Result<Self> {
+        let scalar = u32::from_be_bytes(value.try_into()?);
+        char::from_u32(scalar).with_context(|| format!("invalid char scalar value {:#x}", scalar))
+    }
+}
+
+/// Decodes an unsigned integer of platform-dependent width.
+///
+/// Values are expected as 8 bytes, but 4 bytes are accepted as well, because older
+/// versions encoded `usize` with the width of the writing platform.
+fn decode_unsigned<T: TryFrom<u64>>(value: &[u8], type_name: &str) -> eyre::Result<T> {
+    let wide = match value.len() {
+        4 => u64::from(u32::from_be_bytes(value.try_into()?)),
+        _ => u64::from_be_bytes(value.try_into()?),
+    };
+    T::try_from(wide).map_err(|_| eyre!("value {} does not fit in {}", wide, type_name))
+}
+
+/// Decodes a signed integer of platform-dependent width, see [`decode_unsigned`].
+fn decode_signed<T: TryFrom<i64>>(value: &[u8], type_name: &str) -> eyre::Result<T> {
+    let wide = match value.len() {
+        4 => i64::from(i32::from_be_bytes(value.try_into()?)),
+        _ => i64::from_be_bytes(value.try_into()?),
+    };
+    T::try_from(wide).map_err(|_| eyre!("value {} does not fit in {}", wide, type_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_round_trip() {
+        let value = 1234.5678f64;
+        let decoded = f64::from_anna_value(&value.to_anna_value()).unwrap();
+        assert_eq!(decoded.to_bits(), value.to_bits());
+        let value = -0.5f32;
+        assert_eq!(f32::from_anna_value(&value.to_anna_value()).unwrap(), value);
+        assert!(f64::from_anna_value(&[0; 4]).is_err());
+    }
+
+    #[test]
+    fn pointer_sized_integers_are_width_independent() {
+        assert_eq!(usize::MAX.to_anna_value().len(), 8);
+        assert_eq!(
+            usize::from_anna_value(&42usize.to_anna_value()).unwrap(),
+            42
+        );
+        assert_eq!(
+            isize::from_anna_value(&(-42isize).to_anna_value()).unwrap(),
+            -42
+        );
+        assert_eq!(usize::from_anna_value(&7u32.to_be_bytes()).unwrap(), 7);
+        assert!(usize::from_anna_value(&[0; 2]).is_err());
+
+        // reading 8-byte values on a 32-bit target
+        assert_eq!(
+            decode_unsigned::<u32>(&5u64.to_be_bytes(), "usize").unwrap(),
+            5
+        );
+        assert!(decode_unsigned::<u32>(&u64::MAX.to_be_bytes(), "usize").is_err());
+        assert_eq!(
+            decode_signed::<i32>(&(-5i64).to_be_bytes(), "isize").unwrap(),
+            -5
+        );
+        assert!(decode_signed::<i32>(&i64::MIN.to_be_bytes(), "isize").is_err());
+    }
+
+    #[test]
+    fn wide_integer_and_char_round_trip() {
+        assert_eq!(
+            u128::from_anna_value(&u128::MAX.to_anna_value()).unwrap(),
+            u128::MAX
+        );
+        assert_eq!(
+            i128::from_anna_value(&i128::MIN.to_anna_value()).unwrap(),
+            i128::MIN
+        );
+        assert_eq!(char::from_anna_value(&'😀'.to_anna_value()).unwrap(), '😀');
+        assert!(char::from_anna_value(&0xD800u32.to_be_bytes()).is_err());
+    }
+
+    #[test]
+    fn bool_round_trip() {
+        assert!(bool::from_anna_value(&true.to_anna_value()).unwrap());
+        assert!(!bool::from_anna_value(&false.to_anna_value()).unwrap());
+        assert!(bool::from_anna_value(&[2]).unwrap());
+        assert!(bool::from_anna_value(&[]).is_err());
     }
 }
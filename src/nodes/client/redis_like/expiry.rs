@@ -0,0 +1,93 @@
+//! Expiry metadata for the `SET key value EX seconds` command.
+//!
+//! Anna has no native TTL, so the expiry of a key is stored in a companion key next to
+//! it, together with the timestamp of the value that it applies to. A value that is
+//! overwritten without an expiry, e.g. by `SET`, has a different timestamp and doesn't
+//! expire, and the expiry can be changed without rewriting the value.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anna_api::{lattice::last_writer_wins::Timestamp, ClientKey};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+/// Returns the key that stores the expiry of the given key.
+pub fn companion_key(key: &ClientKey) -> ClientKey {
+    format!("{}\0expiry", &**key).into()
+}
+
+/// The expiry time of a value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expiry {
+    /// The expiry time in milliseconds since the Unix epoch.
+    expires_at: u64,
+    /// The timestamp of the value that expires.
+    version: Timestamp,
+}
+
+impl Expiry {
+    /// Creates the expiry of the value with the given timestamp, `ttl` after `now`.
+    pub fn new(version: Timestamp, ttl: Duration, now: SystemTime) -> eyre::Result<Self> {
+        let expires_at = (now + ttl)
+            .duration_since(UNIX_EPOCH)
+            .context("expiry time is before the Unix epoch")?;
+        let expires_at =
+            u64::try_from(expires_at.as_millis()).context("expiry time is too far away")?;
+        Ok(Self {
+            expires_at,
+            version,
+        })
+    }
+
+    /// Returns the timestamp of the value that expires.
+    pub fn version(&self) -> Timestamp {
+        self.version
+    }
+
+    /// Returns whether the value with the given timestamp has expired at `now`.
+    ///
+    /// Values with other timestamps never expire through this expiry.
+    pub fn expired(&self, version: Timestamp, now: SystemTime) -> bool {
+        version == self.version && self.elapsed(now)
+    }
+
+    /// Returns whether the expiry time has passed at `now`.
+    pub fn elapsed(&self, now: SystemTime) -> bool {
+        UNIX_EPOCH + Duration::from_millis(self.expires_at) <= now
+    }
+
+    /// Returns this expiry moved to `ttl` after `now`.
+    pub fn renewed(&self, ttl: Duration, now: SystemTime) -> eyre::Result<Self> {
+        Self::new(self.version, ttl, now)
+    }
+
+    /// Serializes the expiry as the value of the companion key.
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("failed to serialize expiry")
+    }
+
+    /// Deserializes the value of a companion key.
+    pub fn decode(bytes: &[u8]) -> eyre::Result<Self> {
+        serde_json::from_slice(bytes).context("invalid expiry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_only_its_version() {
+        let now = SystemTime::now();
+        let version = Timestamp::now();
+        let expiry = Expiry::new(version, Duration::from_secs(10), now).unwrap();
+        assert_eq!(Expiry::decode(&expiry.encode()).unwrap(), expiry);
+        assert!(!expiry.expired(version, now));
+        assert!(expiry.expired(version, now + Duration::from_secs(10)));
+
+        let renewed = expiry.renewed(Duration::from_secs(20), now).unwrap();
+        assert!(!renewed.expired(version, now + Duration::from_secs(10)));
+        assert_eq!(renewed.version(), version);
+        assert!(Expiry::decode(b"value").is_err());
+    }
+}
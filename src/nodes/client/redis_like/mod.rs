@@ -1,54 +1,326 @@
 //! Provides Redis-like [`Client`], [`Connection`] and operations, etc.
 
-use anna_api::{AnnaError, ClientKey};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
 
-use crate::ClientConfig;
+use anna_api::ClientKey;
+use eyre::{ensure, Context};
+use futures::future;
+use serde::{de::DeserializeOwned, Serialize};
 
-use self::convert::{FromAnnaValue, ToAnnaValue};
+use crate::{
+    nodes::client::key_does_not_exist, ClientConfig, ClientError, RequestOptions, WithOptions,
+};
 
+pub use self::{
+    batch::{Command, RedisValue},
+    convert::{FromAnnaValue, ToAnnaValue},
+    pipeline::Pipeline,
+    pool::{Pool, PooledConnection},
+    sweeper::ExpirySweeper,
+};
+use self::{expiry::Expiry, sweeper::ExpiringKeys};
+
+mod batch;
+mod bitfield;
 mod convert;
+mod expiry;
+mod pipeline;
+mod pool;
+mod sweeper;
+
+/// The exclusive upper bound of `SETBIT` offsets.
+const MAX_BIT_OFFSET: u64 = 1 << 32;
 
 /// Redis-like client.
+#[derive(Clone)]
 pub struct Client {
     config: ClientConfig,
+    /// The keys with an expiry written through connections of this client.
+    expiring_keys: Arc<Mutex<ExpiringKeys>>,
 }
 
 impl Client {
     /// Creates a new client with given configuration.
     pub fn open(config: ClientConfig) -> eyre::Result<Self> {
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            expiring_keys: Default::default(),
+        })
     }
 
     /// Get an async connection object.
     pub async fn get_async_connection(&self) -> eyre::Result<Connection> {
-        let client = crate::Client::new(self.config.clone())?;
-        Ok(Connection { client })
+        let client = crate::Client::connect(self.config.clone()).await?;
+        Ok(Connection {
+            client,
+            timeout: None,
+            expiring_keys: self.expiring_keys.clone(),
+        })
     }
 }
 
 /// Async Redis-like connection to Anna cluster.
 pub struct Connection {
     client: crate::Client,
+    timeout: Option<Duration>,
+    expiring_keys: Arc<Mutex<ExpiringKeys>>,
 }
 
 impl Connection {
+    /// Overrides the timeout for all operations on this connection.
+    ///
+    /// The override replaces the [`ClientConfig::timeout`] the connection was created
+    /// with, so connections of the same client can use different timeouts. Like the
+    /// configured timeout, it applies to each request of an operation.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(Some(timeout));
+        self
+    }
+
+    /// Sets or clears the timeout override of this connection.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the timeout override of this connection, if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the client with the timeout override of this connection applied.
+    fn client(&mut self) -> WithOptions<'_> {
+        self.client.with_options(RequestOptions {
+            timeout: self.timeout,
+            ..Default::default()
+        })
+    }
+
     /// GET key
     pub async fn get<K, V>(&mut self, key: K) -> eyre::Result<V>
     where
         K: Into<ClientKey>,
         V: FromAnnaValue,
     {
-        let value = self.client.get_lww(key.into()).await?;
+        let value = self.client().get_lww(key.into()).await?;
         V::from_anna_value(&value)
     }
 
+    /// EXISTS key
+    ///
+    /// Returns `Ok(false)` if the key does not exist instead of an error.
+    pub async fn exists<K>(&mut self, key: K) -> eyre::Result<bool>
+    where
+        K: Into<ClientKey>,
+    {
+        Ok(self.client().exists(key.into()).await?)
+    }
+
+    /// GET key, replacing invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// Use this for values that are only displayed, e.g. in logs, where a garbled string
+    /// is preferable to an error. Use `get::<_, String>` to validate that the value is
+    /// UTF-8.
+    pub async fn get_string_lossy<K>(&mut self, key: K) -> eyre::Result<String>
+    where
+        K: Into<ClientKey>,
+    {
+        let value = self.client().get_lww(key.into()).await?;
+        Ok(String::from_utf8_lossy(&value).into_owned())
+    }
+
     /// SET key value
     pub async fn set<K, V>(&mut self, key: K, value: V) -> eyre::Result<()>
     where
         K: Into<ClientKey>,
         V: ToAnnaValue,
     {
-        self.client.put_lww(key.into(), value.to_anna_value()).await
+        self.client()
+            .put_lww(key.into(), value.to_anna_value())
+            .await?;
+        Ok(())
+    }
+
+    /// SET key value EX seconds
+    ///
+    /// Stores the value and the time it expires, `ttl` from now. Read it with
+    /// [`get_ex`][Self::get_ex].
+    ///
+    /// Anna has no native expiry, so the expiry is stored in a companion key and
+    /// enforced lazily: expired values stay stored until they are overwritten and are
+    /// only treated as missing when read, unless an
+    /// [expiry sweeper][Client::spawn_expiry_sweeper] overwrites them. Expiry relies on
+    /// the clocks of the writing and reading hosts.
+    ///
+    /// The value is written before its expiry. If writing the expiry fails, an error is
+    /// returned and the value is stored without expiry.
+    pub async fn set_ex<K, V>(&mut self, key: K, value: V, ttl: Duration) -> eyre::Result<()>
+    where
+        K: Into<ClientKey>,
+        V: ToAnnaValue,
+    {
+        let key = key.into();
+        let mut client = self.client();
+        let version = client
+            .put_lww_versioned(key.clone(), value.to_anna_value())
+            .await?;
+        let expiry = Expiry::new(version, ttl, SystemTime::now())
+            .with_context(|| format!("invalid expiry for key {:?}", key))?;
+        client
+            .put_lww(expiry::companion_key(&key), expiry.encode())
+            .await?;
+        drop(client);
+        self.expiring_keys.lock().unwrap().insert(key);
+        Ok(())
+    }
+
+    /// GET key, respecting the expiry set by [`set_ex`][Self::set_ex].
+    ///
+    /// Returns `None` if the key does not exist or its value has expired. Values without
+    /// an expiry, e.g. written by [`set`][Self::set], are returned as they are.
+    pub async fn get_ex<K, V>(&mut self, key: K) -> eyre::Result<Option<V>>
+    where
+        K: Into<ClientKey>,
+        V: FromAnnaValue,
+    {
+        let key = key.into();
+        let (version, value) = match self.client().get_lww_versioned(key.clone()).await {
+            Ok(versioned) => versioned,
+            Err(ClientError::KeyDoesNotExist) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        match self.get_expiry(&key).await? {
+            Some(expiry) if expiry.expired(version, SystemTime::now()) => Ok(None),
+            _ => V::from_anna_value(&value).map(Some),
+        }
+    }
+
+    /// EXPIRE key seconds, without reading or rewriting the value.
+    ///
+    /// Sets the expiry of a value stored with [`set_ex`][Self::set_ex] to `ttl` from
+    /// now, by only writing its companion key. Returns `false` if the key does not exist
+    /// or has expired.
+    ///
+    /// Keys without an expiry, e.g. written by [`set`][Self::set], are read once to
+    /// check that they exist and to attach the expiry to their current value.
+    pub async fn touch<K>(&mut self, key: K, ttl: Duration) -> eyre::Result<bool>
+    where
+        K: Into<ClientKey>,
+    {
+        let key = key.into();
+        let now = SystemTime::now();
+        let expiry = match self.get_expiry(&key).await? {
+            Some(expiry) if expiry.expired(expiry.version(), now) => return Ok(false),
+            Some(expiry) => expiry.renewed(ttl, now),
+            None => match self.client().get_lww_versioned(key.clone()).await {
+                Ok((version, _)) => Expiry::new(version, ttl, now),
+                Err(ClientError::KeyDoesNotExist) => return Ok(false),
+                Err(err) => return Err(err.into()),
+            },
+        }
+        .with_context(|| format!("invalid expiry for key {:?}", key))?;
+        self.client()
+            .put_lww(expiry::companion_key(&key), expiry.encode())
+            .await?;
+        self.expiring_keys.lock().unwrap().insert(key);
+        Ok(true)
+    }
+
+    /// MGET key [key ...]
+    ///
+    /// Returns the values in the order of the given keys, with `None` for missing keys.
+    pub async fn mget<K, V>(&mut self, keys: Vec<K>) -> eyre::Result<Vec<Option<V>>>
+    where
+        K: Into<ClientKey>,
+        V: FromAnnaValue,
+    {
+        let keys: Vec<ClientKey> = keys.into_iter().map(Into::into).collect();
+        let mut values = self.client().get_lww_many(keys.clone()).await?;
+        let mut results = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let result = match values.get(key) {
+                Some(Ok(bytes)) => Some(V::from_anna_value(bytes)?),
+                Some(Err(ClientError::KeyDoesNotExist)) => None,
+                Some(Err(_)) => return Err(values.remove(key).unwrap().unwrap_err().into()),
+                None => eyre::bail!("no value returned for key {:?}", key),
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// MSET key value [key value ...]
+    ///
+    /// The values are written concurrently.
+    pub async fn mset<K, V>(&mut self, pairs: Vec<(K, V)>) -> eyre::Result<()>
+    where
+        K: Into<ClientKey>,
+        V: ToAnnaValue,
+    {
+        let client = self.client();
+        let puts = pairs.into_iter().map(|(key, value)| {
+            let mut client = client.clone();
+            let value = value.to_anna_value();
+            async move { client.put_lww(key.into(), value).await }
+        });
+        future::try_join_all(puts).await?;
+        Ok(())
+    }
+
+    /// SET key value, storing `value` serialized as JSON.
+    pub async fn set_json<K, V>(&mut self, key: K, value: &V) -> eyre::Result<()>
+    where
+        K: Into<ClientKey>,
+        V: Serialize + ?Sized,
+    {
+        let key = key.into();
+        let bytes = serde_json::to_vec(value)
+            .with_context(|| format!("failed to serialize value for key {:?}", key))?;
+        Ok(self.client().put_lww(key, bytes).await?)
+    }
+
+    /// GET key, deserializing a value stored with [`set_json`][Self::set_json].
+    pub async fn get_json<K, V>(&mut self, key: K) -> eyre::Result<V>
+    where
+        K: Into<ClientKey>,
+        V: DeserializeOwned,
+    {
+        let key = key.into();
+        let bytes = self.client().get_lww(key.clone()).await?;
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to deserialize value of key {:?}", key))
+    }
+
+    /// SET key value, storing `value` serialized with [bincode](https://docs.rs/bincode).
+    ///
+    /// The encoding is more compact than that of [`set_json`][Self::set_json], but it is
+    /// not self-describing, so the value must be read as the same type.
+    #[cfg(feature = "bincode")]
+    pub async fn set_bincode<K, V>(&mut self, key: K, value: &V) -> eyre::Result<()>
+    where
+        K: Into<ClientKey>,
+        V: Serialize + ?Sized,
+    {
+        let key = key.into();
+        let bytes = bincode::serialize(value)
+            .with_context(|| format!("failed to serialize value for key {:?}", key))?;
+        Ok(self.client().put_lww(key, bytes).await?)
+    }
+
+    /// GET key, deserializing a value stored with [`set_bincode`][Self::set_bincode].
+    #[cfg(feature = "bincode")]
+    pub async fn get_bincode<K, V>(&mut self, key: K) -> eyre::Result<V>
+    where
+        K: Into<ClientKey>,
+        V: DeserializeOwned,
+    {
+        let key = key.into();
+        let bytes = self.client().get_lww(key.clone()).await?;
+        bincode::deserialize(&bytes)
+            .with_context(|| format!("failed to deserialize value of key {:?}", key))
     }
 
     /// SETNX key value
@@ -58,18 +330,460 @@ impl Connection {
         V: ToAnnaValue,
     {
         let key = key.into();
-        let mut tx = self.client.begin_transaction();
-        let res = tx.get(key.clone()).await;
-        if res.is_ok() {
-            // exists
-            return Ok(());
+        let mut client = self.client();
+        if !client.exists(key.clone()).await? {
+            client.put_lww(key, value.to_anna_value()).await?;
         }
-        let err_report = res.err().unwrap();
-        if let Some(AnnaError::KeyDoesNotExist) = err_report.downcast_ref() {
-            tx.put(key, value.to_anna_value()).await?;
-            tx.commit().await
-        } else {
-            Err(err_report)
+        Ok(())
+    }
+
+    /// GETSET key value
+    ///
+    /// Sets the new value and returns the previous one, or `None` if the key did not
+    /// exist.
+    ///
+    /// The swap is a read-modify-write of a *last writer wins* value, not an atomic
+    /// operation of the KVS, so concurrent writers of the same key can lose updates or
+    /// observe the same previous value.
+    pub async fn get_set_value<K, V, R>(&mut self, key: K, value: V) -> eyre::Result<Option<R>>
+    where
+        K: Into<ClientKey>,
+        V: ToAnnaValue,
+        R: FromAnnaValue,
+    {
+        let key = key.into();
+        let mut client = self.client();
+        let mut tx = client.begin_transaction();
+        let old = match tx.get(key.clone()).await {
+            Ok(bytes) => Some(R::from_anna_value(&bytes)?),
+            Err(err) if key_does_not_exist(&err) => None,
+            Err(err) => return Err(err),
+        };
+        tx.put(key, value.to_anna_value()).await?;
+        tx.commit().await?;
+        Ok(old)
+    }
+
+    /// INCRBYFLOAT key increment
+    ///
+    /// Returns the new value. The value is stored as an `f64`, see [`FromAnnaValue`],
+    /// and a missing key is treated as `0.0`.
+    ///
+    /// The increment is a read-modify-write of a *last writer wins* value, not a merge
+    /// performed by the KVS, so concurrent increments of the same key can be lost.
+    pub async fn inc_by_float<K>(&mut self, key: K, delta: f64) -> eyre::Result<f64>
+    where
+        K: Into<ClientKey>,
+    {
+        let key = key.into();
+        let mut client = self.client();
+        let mut tx = client.begin_transaction();
+        let value = match tx.get(key.clone()).await {
+            Ok(bytes) => f64::from_anna_value(&bytes)
+                .with_context(|| format!("value of key {:?} is not a float", key))?,
+            Err(err) if key_does_not_exist(&err) => 0.0,
+            Err(err) => return Err(err),
+        } + delta;
+        tx.put(key, value.to_anna_value()).await?;
+        tx.commit().await?;
+        Ok(value)
+    }
+
+    /// APPEND key value
+    ///
+    /// Returns the length of the value after the append. A missing key is treated as
+    /// an empty value.
+    ///
+    /// The append is a read-modify-write of a *last writer wins* value, not a merge
+    /// performed by the KVS, so concurrent appends to the same key can be lost.
+    pub async fn append<K, V>(&mut self, key: K, value: V) -> eyre::Result<usize>
+    where
+        K: Into<ClientKey>,
+        V: ToAnnaValue,
+    {
+        let key = key.into();
+        let mut client = self.client();
+        let mut tx = client.begin_transaction();
+        let mut bytes = match tx.get(key.clone()).await {
+            Ok(bytes) => bytes,
+            Err(err) if key_does_not_exist(&err) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        bytes.extend(value.to_anna_value());
+        let len = bytes.len();
+        tx.put(key, bytes).await?;
+        tx.commit().await?;
+        Ok(len)
+    }
+
+    /// STRLEN key
+    ///
+    /// Returns 0 for a missing key.
+    pub async fn strlen<K>(&mut self, key: K) -> eyre::Result<usize>
+    where
+        K: Into<ClientKey>,
+    {
+        Ok(self.get_bytes_or_empty(key.into()).await?.len())
+    }
+
+    /// SETBIT key offset value
+    ///
+    /// Returns the original value of the bit. The stored value is zero-filled as needed
+    /// when `offset` lies beyond its current end. Like in Redis, the offset must be less
+    /// than 2^32, which limits the value to 512 MiB.
+    ///
+    /// The update is a read-modify-write of a *last writer wins* value, not a merge
+    /// performed by the KVS, so concurrent updates of the same key can be lost.
+    pub async fn setbit<K>(&mut self, key: K, offset: usize, value: bool) -> eyre::Result<bool>
+    where
+        K: Into<ClientKey>,
+    {
+        ensure!(
+            (offset as u64) < MAX_BIT_OFFSET,
+            "bit offset {} is out of range",
+            offset
+        );
+        let key = key.into();
+        let mut client = self.client();
+        let mut tx = client.begin_transaction();
+        let mut bytes = match tx.get(key.clone()).await {
+            Ok(bytes) => bytes,
+            Err(err) if key_does_not_exist(&err) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let old = bitfield::set_bit(&mut bytes, offset, value);
+        tx.put(key, bytes).await?;
+        tx.commit().await?;
+        Ok(old)
+    }
+
+    /// GETBIT key offset
+    ///
+    /// Bits beyond the end of the stored value, or of a missing key, are 0.
+    pub async fn getbit<K>(&mut self, key: K, offset: usize) -> eyre::Result<bool>
+    where
+        K: Into<ClientKey>,
+    {
+        let bytes = self.get_bytes_or_empty(key.into()).await?;
+        Ok(bitfield::get_bit(&bytes, offset))
+    }
+
+    /// BITCOUNT key
+    pub async fn bitcount<K>(&mut self, key: K) -> eyre::Result<usize>
+    where
+        K: Into<ClientKey>,
+    {
+        let bytes = self.get_bytes_or_empty(key.into()).await?;
+        Ok(bitfield::count_bits(&bytes))
+    }
+
+    /// SISMEMBER key member
+    ///
+    /// Returns `false` for a missing key. Anna can't query parts of a set, so the whole
+    /// set is fetched; prefer [`crate::Client::get_set`] when checking many members.
+    pub async fn s_is_member<K, V>(&mut self, key: K, member: V) -> eyre::Result<bool>
+    where
+        K: Into<ClientKey>,
+        V: ToAnnaValue,
+    {
+        let set = self.get_set_or_empty(key.into()).await?;
+        Ok(set.contains(&member.to_anna_value()))
+    }
+
+    /// SCARD key
+    ///
+    /// Returns 0 for a missing key. Like [`s_is_member`][Self::s_is_member], this
+    /// fetches the whole set.
+    pub async fn s_card<K>(&mut self, key: K) -> eyre::Result<usize>
+    where
+        K: Into<ClientKey>,
+    {
+        Ok(self.get_set_or_empty(key.into()).await?.len())
+    }
+
+    /// Returns the stored set, or an empty set if the key does not exist.
+    async fn get_set_or_empty(&mut self, key: ClientKey) -> eyre::Result<HashSet<Vec<u8>>> {
+        match self.client().get_set(key).await {
+            Ok(set) => Ok(set),
+            Err(ClientError::KeyDoesNotExist) => Ok(HashSet::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn get_bytes_or_empty(&mut self, key: ClientKey) -> eyre::Result<Vec<u8>> {
+        Ok(self.get_bytes(key).await?.unwrap_or_default())
+    }
+
+    /// Returns the expiry of the given key, if it has one.
+    async fn get_expiry(&mut self, key: &ClientKey) -> eyre::Result<Option<Expiry>> {
+        let companion = expiry::companion_key(key);
+        match self.get_bytes(companion).await? {
+            Some(bytes) => Expiry::decode(&bytes)
+                .with_context(|| format!("failed to read expiry of key {:?}", key))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the stored bytes, or `None` if the key does not exist.
+    async fn get_bytes(&mut self, key: ClientKey) -> eyre::Result<Option<Vec<u8>>> {
+        match self.client().get_lww(key).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ClientError::KeyDoesNotExist) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Instant};
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, silent_addr, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn connection_timeout_override() {
+        let addr = silent_addr().await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client
+            .get_async_connection()
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        cache_key_address(&mut con.client, &"slow".into(), addr);
+
+        let start = Instant::now();
+        let err = con.get::<_, Vec<u8>>("slow").await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(matches!(ClientError::from(err), ClientError::Timeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // the timed out request is no longer awaited
+        assert!(con.client.response_promises.lock().await.is_empty());
+        assert_eq!(con.client.config().timeout, test_config().timeout);
+    }
+
+    #[tokio::test]
+    async fn set_nx_only_sets_missing_keys() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"key".into(), kvs.addr);
+
+        con.set_nx("key", "first").await.unwrap();
+        con.set_nx("key", "second").await.unwrap();
+        assert_eq!(con.get::<_, String>("key").await.unwrap(), "first");
+    }
+
+    #[tokio::test]
+    async fn lossy_and_strict_strings() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"caf\xe9");
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"key".into(), kvs.addr);
+
+        assert_eq!(con.get_string_lossy("key").await.unwrap(), "caf\u{fffd}");
+        assert!(con.get::<_, String>("key").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_set_value_returns_previous_value() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"key".into(), kvs.addr);
+
+        let old: Option<String> = con.get_set_value("key", "first").await.unwrap();
+        assert_eq!(old, None);
+        let old: Option<String> = con.get_set_value("key", "second").await.unwrap();
+        assert_eq!(old.as_deref(), Some("first"));
+        assert_eq!(con.get::<_, String>("key").await.unwrap(), "second");
+    }
+
+    #[tokio::test]
+    async fn mget_preserves_order_and_reports_missing_keys() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["a", "b", "c"] {
+            cache_key_address(&mut con.client, &key.into(), kvs.addr);
         }
+
+        con.mset(vec![("a", "1"), ("c", "3")]).await.unwrap();
+        let values: Vec<Option<String>> = con.mget(vec!["a", "b", "c"]).await.unwrap();
+        assert_eq!(values, vec![Some("1".into()), None, Some("3".into())]);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[tokio::test]
+    async fn setbit_rejects_offsets_beyond_limit() {
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+
+        let err = con.setbit("bits", 1 << 32, true).await.unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        // rejected before any request is sent
+        assert!(con.client.response_promises.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn inc_by_float_sums_increments() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"sum".into(), kvs.addr);
+
+        assert_eq!(con.inc_by_float("sum", 1.5).await.unwrap(), 1.5);
+        assert_eq!(con.inc_by_float("sum", 2.25).await.unwrap(), 3.75);
+        assert_eq!(con.inc_by_float("sum", -0.75).await.unwrap(), 3.0);
+        assert_eq!(con.get::<_, f64>("sum").await.unwrap(), 3.0);
+    }
+
+    #[tokio::test]
+    async fn append_to_fresh_key() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"log".into(), kvs.addr);
+
+        assert_eq!(con.strlen("log").await.unwrap(), 0);
+        assert_eq!(con.append("log", "foo").await.unwrap(), 3);
+        assert_eq!(con.append("log", "bar").await.unwrap(), 6);
+        assert_eq!(con.get::<_, String>("log").await.unwrap(), "foobar");
+        assert_eq!(con.strlen("log").await.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn json_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct User {
+            name: String,
+            age: u32,
+        }
+
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        cache_key_address(&mut con.client, &"user:1".into(), kvs.addr);
+
+        let user = User {
+            name: "alice".into(),
+            age: 42,
+        };
+        con.set_json("user:1", &user).await.unwrap();
+        assert_eq!(con.get_json::<_, User>("user:1").await.unwrap(), user);
+        let err = con.get_json::<_, u32>("user:1").await.unwrap_err();
+        assert!(err.to_string().contains("user:1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn set_ex_expires_lazily() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        let key: ClientKey = "session".into();
+        for key in [key.clone(), expiry::companion_key(&key)] {
+            cache_key_address(&mut con.client, &key, kvs.addr);
+        }
+
+        con.set_ex("session", "token", Duration::from_millis(100))
+            .await
+            .unwrap();
+        let value: Option<String> = con.get_ex("session").await.unwrap();
+        assert_eq!(value.as_deref(), Some("token"));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let value: Option<String> = con.get_ex("session").await.unwrap();
+        assert_eq!(value, None);
+        assert!(con.exists("session").await.unwrap());
+
+        // a plain SET stores the value without expiry
+        con.set("session", "persistent").await.unwrap();
+        let value: Option<String> = con.get_ex("session").await.unwrap();
+        assert_eq!(value.as_deref(), Some("persistent"));
+    }
+
+    #[tokio::test]
+    async fn touch_moves_expiry_without_rewriting_value() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        let key: ClientKey = "session".into();
+        let missing: ClientKey = "missing".into();
+        for key in [&key, &missing] {
+            cache_key_address(&mut con.client, key, kvs.addr);
+            cache_key_address(&mut con.client, &expiry::companion_key(key), kvs.addr);
+        }
+
+        con.set_ex("session", "token", Duration::from_millis(100))
+            .await
+            .unwrap();
+        let before = con.client.get_lww_versioned(key.clone()).await.unwrap();
+        let expiry_before = con.get_expiry(&key).await.unwrap().unwrap();
+
+        let requests = kvs.requests.load(Ordering::SeqCst);
+        assert!(con.touch("session", Duration::from_secs(10)).await.unwrap());
+        // one read and one write of the companion key
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), requests + 2);
+        let expiry_after = con.get_expiry(&key).await.unwrap().unwrap();
+        assert_ne!(expiry_after, expiry_before);
+        assert_eq!(con.client.get_lww_versioned(key).await.unwrap(), before);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let value: Option<String> = con.get_ex("session").await.unwrap();
+        assert_eq!(value.as_deref(), Some("token"));
+        assert!(!con.touch("missing", Duration::from_secs(10)).await.unwrap());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn bincode_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Order {
+            id: u64,
+            items: Vec<(String, u32)>,
+            note: Option<String>,
+        }
+
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["order:1", "garbage"] {
+            cache_key_address(&mut con.client, &key.into(), kvs.addr);
+        }
+
+        let order = Order {
+            id: 1,
+            items: vec![("apple".into(), 3), ("pear".into(), 1)],
+            note: None,
+        };
+        con.set_bincode("order:1", &order).await.unwrap();
+        assert_eq!(con.get_bincode::<_, Order>("order:1").await.unwrap(), order);
+
+        kvs.put_lww("garbage", b"\xff\xff");
+        let err = con.get_bincode::<_, Order>("garbage").await.unwrap_err();
+        assert!(err.to_string().contains("garbage"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn set_membership_and_cardinality() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["set", "missing"] {
+            cache_key_address(&mut con.client, &key.into(), kvs.addr);
+        }
+
+        let set = ["a", "b", "c"]
+            .iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
+        con.client.put_set("set".into(), set).await.unwrap();
+        assert!(con.s_is_member("set", "b").await.unwrap());
+        assert!(!con.s_is_member("set", "d").await.unwrap());
+        assert_eq!(con.s_card("set").await.unwrap(), 3);
+        assert!(!con.s_is_member("missing", "a").await.unwrap());
+        assert_eq!(con.s_card("missing").await.unwrap(), 0);
     }
 }
@@ -0,0 +1,130 @@
+//! Provides [`Pipeline`] for dispatching independent commands concurrently.
+
+use std::collections::HashMap;
+
+use anna_api::ClientKey;
+use futures::future;
+
+use super::{Command, Connection, RedisValue, ToAnnaValue};
+
+/// A queue of commands that are executed together by [`Pipeline::execute`].
+///
+/// Created through [`Connection::pipeline`].
+pub struct Pipeline<'a> {
+    connection: &'a mut Connection,
+    commands: Vec<Command>,
+}
+
+impl Connection {
+    /// Starts a pipeline of commands on this connection.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline {
+            connection: self,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Pipeline<'a> {
+    /// Queues the given command.
+    pub fn cmd(&mut self, command: Command) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Queues a GET.
+    pub fn get<K: Into<ClientKey>>(&mut self, key: K) -> &mut Self {
+        self.cmd(Command::Get(key.into()))
+    }
+
+    /// Queues a SET.
+    pub fn set<K: Into<ClientKey>, V: ToAnnaValue>(&mut self, key: K, value: V) -> &mut Self {
+        self.cmd(Command::Set(key.into(), value.to_anna_value()))
+    }
+
+    /// Queues a SETNX.
+    pub fn set_nx<K: Into<ClientKey>, V: ToAnnaValue>(&mut self, key: K, value: V) -> &mut Self {
+        self.cmd(Command::SetNx(key.into(), value.to_anna_value()))
+    }
+
+    /// Executes the queued commands and returns their results in the queued order.
+    ///
+    /// Commands on the same key run in the queued order, commands on different keys are
+    /// dispatched concurrently. Fails with the first error of any command.
+    pub async fn execute(&mut self) -> eyre::Result<Vec<RedisValue>> {
+        let commands = std::mem::take(&mut self.commands);
+        let len = commands.len();
+
+        let mut groups: Vec<(Vec<usize>, Vec<Command>)> = Vec::new();
+        let mut group_of_key: HashMap<ClientKey, usize> = HashMap::new();
+        for (index, command) in commands.into_iter().enumerate() {
+            let group = *group_of_key
+                .entry(command.key().clone())
+                .or_insert_with(|| {
+                    groups.push((Vec::new(), Vec::new()));
+                    groups.len() - 1
+                });
+            groups[group].0.push(index);
+            groups[group].1.push(command);
+        }
+
+        let (indices, batches): (Vec<_>, Vec<_>) = groups.into_iter().unzip();
+        let runs = batches.into_iter().map(|batch| {
+            let mut connection = Connection {
+                client: self.connection.client.clone(),
+                timeout: self.connection.timeout,
+                expiring_keys: self.connection.expiring_keys.clone(),
+            };
+            async move { connection.exec_batch(batch).await }
+        });
+        let outcomes = future::join_all(runs).await;
+
+        let mut results = vec![RedisValue::Nil; len];
+        for (indices, outcome) in indices.into_iter().zip(outcomes) {
+            for (index, result) in indices.into_iter().zip(outcome?) {
+                results[index] = result;
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, test_config, MockKvs},
+        redis_like::Client,
+    };
+
+    #[tokio::test]
+    async fn pipeline_results_in_queued_order() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["a", "b", "c"] {
+            cache_key_address(&mut con.client, &key.into(), kvs.addr);
+        }
+
+        let results = con
+            .pipeline()
+            .set("a", "1")
+            .set("b", "2")
+            .set("c", "3")
+            .get("b")
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![
+                RedisValue::Okay,
+                RedisValue::Okay,
+                RedisValue::Okay,
+                RedisValue::Data(b"2".to_vec()),
+            ]
+        );
+    }
+}
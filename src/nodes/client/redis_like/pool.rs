@@ -0,0 +1,119 @@
+//! Provides [`Pool`], a fixed-size pool of reusable [`Connection`]s.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use eyre::ensure;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{Client, Connection};
+
+/// A pool of at most `size` connections, created by [`Client::pool`].
+///
+/// Connections are created on demand and reused once they are returned, so their
+/// address caches and TCP connections stay warm. The pool can be cloned cheaply and
+/// shared between tasks.
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    client: Client,
+    permits: Arc<Semaphore>,
+    idle: std::sync::Mutex<Vec<Connection>>,
+}
+
+impl Client {
+    /// Creates a pool of at most `size` connections.
+    pub fn pool(&self, size: usize) -> eyre::Result<Pool> {
+        ensure!(size > 0, "pool size must be at least 1");
+        Ok(Pool {
+            inner: Arc::new(PoolInner {
+                client: self.clone(),
+                permits: Arc::new(Semaphore::new(size)),
+                idle: Default::default(),
+            }),
+        })
+    }
+}
+
+impl Pool {
+    /// Takes a connection from the pool, waiting until one is available.
+    ///
+    /// A new connection is created if the pool has not yet reached its size and no idle
+    /// connection is available. The connection returns to the pool when dropped.
+    pub async fn acquire(&self) -> eyre::Result<PooledConnection> {
+        let permit = self.inner.permits.clone().acquire_owned().await?;
+        let idle = self.inner.idle.lock().unwrap().pop();
+        let connection = match idle {
+            Some(connection) => connection,
+            None => self.inner.client.get_async_connection().await?,
+        };
+        Ok(PooledConnection {
+            connection: Some(connection),
+            pool: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+/// A [`Connection`] borrowed from a [`Pool`].
+///
+/// Dereferences to the connection and returns it to the pool when dropped.
+pub struct PooledConnection {
+    connection: Option<Connection>,
+    pool: Arc<PoolInner>,
+    // released after the connection was returned in `drop`
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.connection.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.idle.lock().unwrap().push(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::nodes::client::tests::test_config;
+
+    #[tokio::test]
+    async fn pool_reuses_connections() {
+        fn assert_shareable<T: Clone + Send + Sync>() {}
+        assert_shareable::<Pool>();
+
+        let pool = Client::open(test_config()).unwrap().pool(1).unwrap();
+
+        let first = pool.acquire().await.unwrap();
+        let client_id = first.client.debug_state().await.client_id;
+        // the only connection is in use
+        let blocked = tokio::time::timeout(Duration::from_millis(50), pool.acquire()).await;
+        assert!(blocked.is_err());
+
+        drop(first);
+        let second = pool.acquire().await.unwrap();
+        assert_eq!(second.client.debug_state().await.client_id, client_id);
+    }
+}
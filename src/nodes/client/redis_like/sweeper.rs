@@ -0,0 +1,200 @@
+//! Provides [`ExpirySweeper`], which proactively overwrites expired values.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, SystemTime},
+};
+
+use anna_api::{lattice::LastWriterWinsLattice, ClientKey, LatticeValue};
+use eyre::ensure;
+use tokio::task::JoinHandle;
+
+use super::{Client, Connection};
+
+/// Settings of the background sweep of expired keys, see
+/// [`Client::spawn_expiry_sweeper`].
+#[derive(Debug, Clone)]
+pub struct ExpirySweeper {
+    /// The time between two sweep passes.
+    pub interval: Duration,
+    /// The maximum number of keys checked per sweep pass.
+    pub batch_size: usize,
+}
+
+impl Default for ExpirySweeper {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            batch_size: 100,
+        }
+    }
+}
+
+/// The keys with an expiry that were written through the connections of a [`Client`],
+/// in the order in which they are swept.
+#[derive(Debug, Default)]
+pub(super) struct ExpiringKeys {
+    queue: VecDeque<ClientKey>,
+    known: HashSet<ClientKey>,
+}
+
+impl ExpiringKeys {
+    /// Adds the key to the end of the queue, unless it is already known.
+    pub fn insert(&mut self, key: ClientKey) {
+        if self.known.insert(key.clone()) {
+            self.queue.push_back(key);
+        }
+    }
+
+    /// Removes and returns up to `size` keys from the front of the queue.
+    fn take(&mut self, size: usize) -> Vec<ClientKey> {
+        let count = size.min(self.queue.len());
+        let keys: Vec<_> = self.queue.drain(..count).collect();
+        for key in &keys {
+            self.known.remove(key);
+        }
+        keys
+    }
+}
+
+impl Client {
+    /// Spawns a task that periodically overwrites the values of expired keys, so that
+    /// their space is reclaimed even if they are never read again.
+    ///
+    /// Only the keys written with [`Connection::set_ex`] or [`Connection::touch`]
+    /// through connections of this client are swept. Each pass checks up to
+    /// `batch_size` of them, oldest first, by reading their expiry. Anna has no delete,
+    /// so the value of an expired key is replaced by an empty value with the timestamp
+    /// of the expired value: it can't overwrite a newer value of the key, and
+    /// [`Connection::get_ex`] still treats the key as missing. The companion key of the
+    /// expiry is left in place.
+    ///
+    /// The sweep adds load to the cluster: every pass sends a read per checked key and a
+    /// write per expired key, also for keys that would never be read again. Choose the
+    /// interval and batch size accordingly. The lazy expiry on read is unaffected.
+    ///
+    /// The task runs until the returned handle is aborted.
+    pub async fn spawn_expiry_sweeper(
+        &self,
+        sweeper: ExpirySweeper,
+    ) -> eyre::Result<JoinHandle<()>> {
+        ensure!(sweeper.batch_size > 0, "batch size must be at least 1");
+        let mut connection = self.get_async_connection().await?;
+        Ok(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(sweeper.interval).await;
+                let now = SystemTime::now();
+                match connection.sweep_expired(sweeper.batch_size, now).await {
+                    Ok(swept) => tracing::trace!("Swept {} expired keys", swept),
+                    Err(err) => tracing::warn!("Failed to sweep expired keys: {:#}", err),
+                }
+            }
+        }))
+    }
+}
+
+impl Connection {
+    /// Checks the next `batch_size` known expiring keys and overwrites the values of
+    /// those that expired at `now`.
+    ///
+    /// Returns the number of overwritten values. Keys that have not expired yet are
+    /// checked again in a later pass; keys that failed to be checked are retried, and
+    /// the first error is returned after the whole batch was processed.
+    pub(super) async fn sweep_expired(
+        &mut self,
+        batch_size: usize,
+        now: SystemTime,
+    ) -> eyre::Result<usize> {
+        let keys = self.expiring_keys.lock().unwrap().take(batch_size);
+        let mut swept = 0;
+        let mut result = Ok(());
+        for key in keys {
+            match self.sweep_key(&key, now).await {
+                Ok(Some(true)) => swept += 1,
+                Ok(Some(false)) => self.expiring_keys.lock().unwrap().insert(key),
+                Ok(None) => {}
+                Err(err) => {
+                    self.expiring_keys.lock().unwrap().insert(key);
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result.map(|()| swept)
+    }
+
+    /// Overwrites the value of the key if it expired at `now`.
+    ///
+    /// Returns whether the value was overwritten, or `None` if the key has no expiry.
+    async fn sweep_key(&mut self, key: &ClientKey, now: SystemTime) -> eyre::Result<Option<bool>> {
+        let expiry = match self.get_expiry(key).await? {
+            Some(expiry) => expiry,
+            None => return Ok(None),
+        };
+        if !expiry.elapsed(now) {
+            return Ok(Some(false));
+        }
+        let tombstone = LastWriterWinsLattice::from_pair(expiry.version(), Vec::new());
+        self.client()
+            .put_lattice(key.clone(), LatticeValue::Lww(tombstone))
+            .await?;
+        tracing::debug!("Overwrote expired value of key {:?}", key);
+        Ok(Some(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anna_api::lattice::Lattice;
+
+    use super::*;
+    use crate::{
+        nodes::client::redis_like::expiry,
+        nodes::client::tests::{cache_key_address, test_config, MockKvs},
+        Key,
+    };
+
+    #[tokio::test]
+    async fn sweep_overwrites_expired_values_without_reads() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let client = Client::open(test_config()).unwrap();
+        let mut con = client.get_async_connection().await.unwrap();
+        for key in ["short", "long"] {
+            let key: ClientKey = key.into();
+            cache_key_address(&mut con.client, &key, kvs.addr);
+            cache_key_address(&mut con.client, &expiry::companion_key(&key), kvs.addr);
+        }
+        con.set_ex("short", "a", Duration::from_secs(10))
+            .await
+            .unwrap();
+        con.set_ex("long", "b", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        let stored = |key: &str| match kvs.store.lock().unwrap().get(&Key::Client(key.into())) {
+            Some(LatticeValue::Lww(lattice)) => lattice.reveal().value().clone(),
+            other => panic!("unexpected value {:?}", other),
+        };
+
+        // a sweep before the expiry keeps both values
+        assert_eq!(con.sweep_expired(10, SystemTime::now()).await.unwrap(), 0);
+        let later = SystemTime::now() + Duration::from_secs(20);
+        assert_eq!(con.sweep_expired(10, later).await.unwrap(), 1);
+        assert!(stored("short").is_empty());
+        assert_eq!(stored("long"), b"b");
+        // the swept key is forgotten, the other one is checked again
+        let keys = &con.expiring_keys.lock().unwrap().queue;
+        assert_eq!(keys, &[ClientKey::from("long")]);
+    }
+
+    #[test]
+    fn takes_batches_in_insertion_order() {
+        let mut keys = ExpiringKeys::default();
+        for key in ["a", "b", "a", "c"] {
+            keys.insert(key.into());
+        }
+        assert_eq!(keys.take(2), vec!["a".into(), "b".into()]);
+        keys.insert("a".into());
+        assert_eq!(keys.take(5), vec!["c".into(), "a".into()]);
+    }
+}
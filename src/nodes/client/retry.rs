@@ -0,0 +1,178 @@
+//! Provides [`RetryPolicy`] for retrying requests that failed with transient errors, and
+//! [`ReconnectBackoff`] for spacing out connection attempts to unreachable nodes.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::Client;
+
+/// Describes how often and when a [`Client`] retries failed requests.
+///
+/// Requests that fail to be sent or receive no response in time are retried after an
+/// exponentially growing delay. Before each retry, the cached addresses of the key are
+/// dropped so that they are queried from the routing tier again. All operations of the
+/// client are idempotent, so retries can't apply a write twice.
+///
+/// The default value performs no retries.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts per request, including the first one.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The factor by which the delay grows with each retry.
+    pub multiplier: f64,
+    /// The upper bound of the delay.
+    pub max_delay: Duration,
+    /// The fraction by which each delay is randomly varied, between `0.0` and `1.0`.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(50),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(5),
+            jitter: 0.1,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the given retry, starting at 1.
+    pub(super) fn delay(&self, retry: u32) -> Duration {
+        exponential_delay(
+            self.base_delay,
+            self.multiplier,
+            self.max_delay,
+            self.jitter,
+            retry,
+        )
+    }
+}
+
+/// Describes how long a [`Client`] waits before connecting to a node again after
+/// connection attempts to it failed.
+///
+/// After the `n`-th consecutive failure, further connection attempts to the address fail
+/// immediately for an exponentially growing delay, instead of repeatedly trying to
+/// reach a node that is still down. Once the delay has passed, the next use of the
+/// address tries to connect again, and a successful connection resets the backoff.
+///
+/// A zero `base_delay` disables the backoff.
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    /// The delay after the first failed connection attempt.
+    pub base_delay: Duration,
+    /// The factor by which the delay grows with each further failure.
+    pub multiplier: f64,
+    /// The upper bound of the delay.
+    pub max_delay: Duration,
+    /// The fraction by which each delay is randomly varied, between `0.0` and `1.0`.
+    pub jitter: f64,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(10),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectBackoff {
+    /// Returns the delay after the given number of consecutive failures, starting at 1.
+    pub(super) fn delay(&self, failures: u32) -> Duration {
+        exponential_delay(
+            self.base_delay,
+            self.multiplier,
+            self.max_delay,
+            self.jitter,
+            failures,
+        )
+    }
+}
+
+/// Returns `base * multiplier^(n - 1)`, randomly varied by `jitter` and capped at `max`.
+fn exponential_delay(
+    base: Duration,
+    multiplier: f64,
+    max: Duration,
+    jitter: f64,
+    n: u32,
+) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0);
+    let factor = multiplier.powi(n.saturating_sub(1) as i32)
+        * (1.0 + rand::thread_rng().gen_range(-jitter..=jitter));
+    let secs = (base.as_secs_f64() * factor).min(max.as_secs_f64());
+    Duration::from_secs_f64(secs.max(0.0))
+}
+
+impl Client {
+    /// Sets the policy for retrying requests that failed with transient errors.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets the backoff of connection attempts to nodes that could not be connected to.
+    ///
+    /// The backoff is shared by all handles of the client, like its connections.
+    pub fn set_reconnect_backoff(&mut self, backoff: ReconnectBackoff) {
+        *self.connections.backoff.lock().unwrap() = backoff;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nodes::client::tests::{cache_key_address, dead_addr, mock_routing, test_config, MockKvs},
+        topics::KvsThread,
+        ClientConfig,
+    };
+
+    #[test]
+    fn delay_grows_exponentially() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let delays: Vec<_> = (1..=4).map(|retry| policy.delay(retry)).collect();
+        assert_eq!(delays, [10, 20, 40, 50].map(Duration::from_millis).to_vec());
+    }
+
+    #[tokio::test]
+    async fn retries_with_fresh_address() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"value");
+        let kvs_thread = KvsThread {
+            node_id: "kvs".into(),
+            thread_id: 0,
+        };
+        let port = mock_routing(vec![(kvs_thread, kvs.addr)]).await;
+        let mut client = Client::new(ClientConfig {
+            routing_port_base: port,
+            ..test_config()
+        })
+        .unwrap();
+        // the cached address is stale, so the first attempt fails
+        let dead = dead_addr().await;
+        cache_key_address(&mut client, &"key".into(), dead);
+        assert!(client.get_lww("key".into()).await.is_err());
+
+        client.set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+        assert_eq!(client.get_lww("key".into()).await.unwrap(), b"value");
+    }
+}
@@ -1,15 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anna_api::{
-    lattice::{last_writer_wins::Timestamp, LastWriterWinsLattice},
+    lattice::{last_writer_wins::Timestamp, LastWriterWinsLattice, SetLattice},
     ClientKey, LatticeValue,
 };
+use eyre::bail;
 
-use crate::Client;
+use crate::{Client, ClientError};
 
 pub struct ReadCommittedTransaction<'a> {
     client: &'a mut Client,
-    write_buffer: HashMap<ClientKey, Vec<u8>>,
+    write_buffer: HashMap<ClientKey, BufferedWrite>,
+    /// Whether the transaction was committed or aborted.
+    finished: bool,
+}
+
+/// A write that is buffered until the transaction commits.
+enum BufferedWrite {
+    /// A *last writer wins* value that replaces the stored value.
+    Lww(Vec<u8>),
+    /// Elements that are merged into the stored set.
+    Set(HashSet<Vec<u8>>),
 }
 
 impl<'a> ReadCommittedTransaction<'a> {
@@ -17,32 +28,228 @@ impl<'a> ReadCommittedTransaction<'a> {
         Self {
             client,
             write_buffer: HashMap::new(),
+            finished: false,
         }
     }
 
     pub async fn get(&mut self, key: ClientKey) -> eyre::Result<Vec<u8>> {
-        if let Some(value) = self.write_buffer.get(&key) {
-            Ok(value.clone())
-        } else {
-            self.client.get_lww(key).await
+        match self.write_buffer.get(&key) {
+            Some(BufferedWrite::Lww(value)) => Ok(value.clone()),
+            Some(BufferedWrite::Set(_)) => {
+                bail!("key {:?} has buffered set additions, not a lww value", key)
+            }
+            None => Ok(self.client.get_lww(key).await?),
+        }
+    }
+
+    /// Reads a set value, including the elements added earlier in this transaction.
+    pub async fn get_set(&mut self, key: ClientKey) -> eyre::Result<HashSet<Vec<u8>>> {
+        let added = match self.write_buffer.get(&key) {
+            Some(BufferedWrite::Set(added)) => added.clone(),
+            Some(BufferedWrite::Lww(_)) => {
+                bail!("key {:?} has a buffered lww value, not a set", key)
+            }
+            None => return Ok(self.client.get_set(key).await?),
+        };
+        let mut set = match self.client.get_set(key).await {
+            Ok(set) => set,
+            Err(ClientError::KeyDoesNotExist) => HashSet::new(),
+            Err(err) => return Err(err.into()),
+        };
+        set.extend(added);
+        Ok(set)
+    }
+
+    /// Reads multiple keys, serving buffered writes from the write buffer and fetching
+    /// the remaining keys from the cluster with [`Client::get_lww_many`].
+    ///
+    /// Errors are reported per key.
+    pub async fn get_lww_many(
+        &mut self,
+        keys: Vec<ClientKey>,
+    ) -> eyre::Result<HashMap<ClientKey, Result<Vec<u8>, ClientError>>> {
+        let (buffered, fetched): (Vec<_>, Vec<_>) = keys
+            .into_iter()
+            .partition(|key| matches!(self.write_buffer.get(key), Some(BufferedWrite::Lww(_))));
+        let mut values = self.client.get_lww_many(fetched).await?;
+        for key in buffered {
+            if let Some(BufferedWrite::Lww(value)) = self.write_buffer.get(&key) {
+                values.insert(key.clone(), Ok(value.clone()));
+            }
         }
+        Ok(values)
     }
 
     pub async fn put(&mut self, key: ClientKey, value: Vec<u8>) -> eyre::Result<()> {
-        self.write_buffer.insert(key, value);
+        self.write_buffer.insert(key, BufferedWrite::Lww(value));
         Ok(())
     }
 
-    pub async fn commit(self) -> eyre::Result<()> {
+    /// Buffers the addition of the given elements to the set value of the given key.
+    pub async fn add_set(
+        &mut self,
+        key: ClientKey,
+        elements: HashSet<Vec<u8>>,
+    ) -> eyre::Result<()> {
+        match self.write_buffer.get_mut(&key) {
+            Some(BufferedWrite::Set(added)) => added.extend(elements),
+            Some(BufferedWrite::Lww(_)) => {
+                bail!("key {:?} has a buffered lww value, not a set", key)
+            }
+            None => {
+                self.write_buffer.insert(key, BufferedWrite::Set(elements));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn commit(mut self) -> eyre::Result<()> {
+        self.finished = true;
         let commit_time = Timestamp::now();
-        for (key, value) in self.write_buffer.into_iter() {
-            self.client
-                .put_lattice(
-                    key,
-                    LatticeValue::Lww(LastWriterWinsLattice::from_pair(commit_time, value)),
-                )
-                .await?;
+        for (key, write) in std::mem::take(&mut self.write_buffer) {
+            let lattice = match write {
+                BufferedWrite::Lww(value) => {
+                    LatticeValue::Lww(LastWriterWinsLattice::from_pair(commit_time, value))
+                }
+                BufferedWrite::Set(added) => LatticeValue::Set(SetLattice::new(added)),
+            };
+            self.client.put_lattice(key, lattice).await?;
         }
         Ok(())
     }
+
+    /// Discards the buffered writes without sending anything.
+    pub fn abort(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for ReadCommittedTransaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished && !self.write_buffer.is_empty() {
+            tracing::warn!(
+                "transaction with {} buffered writes dropped without commit or abort",
+                self.write_buffer.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use anna_api::ClientKey;
+
+    use crate::{
+        nodes::client::tests::{cache_key_address, test_config, MockKvs, SpanCapture},
+        Client, ClientError,
+    };
+
+    #[tokio::test]
+    async fn get_lww_many_prefers_write_buffer() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("a", b"stored a");
+        kvs.put_lww("b", b"stored b");
+        let mut client = Client::new(test_config()).unwrap();
+        for key in ["a", "b", "c", "d"] {
+            cache_key_address(&mut client, &key.into(), kvs.addr);
+        }
+
+        let mut tx = client.begin_transaction();
+        tx.put("a".into(), b"buffered a".to_vec()).await.unwrap();
+        tx.put("c".into(), b"buffered c".to_vec()).await.unwrap();
+        let mut values = tx
+            .get_lww_many(vec!["a".into(), "b".into(), "c".into(), "d".into()])
+            .await
+            .unwrap();
+
+        assert_eq!(values.remove(&"a".into()).unwrap().unwrap(), b"buffered a");
+        assert_eq!(values.remove(&"b".into()).unwrap().unwrap(), b"stored b");
+        assert_eq!(values.remove(&"c".into()).unwrap().unwrap(), b"buffered c");
+        assert!(matches!(
+            values.remove(&"d".into()).unwrap(),
+            Err(ClientError::KeyDoesNotExist)
+        ));
+        // only `b` and `d` are fetched from the cluster
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn set_additions_are_visible_before_commit() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let key: ClientKey = "set".into();
+        cache_key_address(&mut client, &key, kvs.addr);
+        client
+            .put_set(key.clone(), HashSet::from([b"a".to_vec()]))
+            .await
+            .unwrap();
+
+        let mut tx = client.begin_transaction();
+        tx.add_set(key.clone(), HashSet::from([b"b".to_vec()]))
+            .await
+            .unwrap();
+        tx.add_set(key.clone(), HashSet::from([b"c".to_vec()]))
+            .await
+            .unwrap();
+        let expected = HashSet::from([b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert_eq!(tx.get_set(key.clone()).await.unwrap(), expected);
+        tx.commit().await.unwrap();
+
+        assert_eq!(client.get_set(key).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn abort_discards_buffered_writes() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        kvs.put_lww("key", b"before");
+        let mut client = Client::new(test_config()).unwrap();
+        cache_key_address(&mut client, &"key".into(), kvs.addr);
+
+        let mut tx = client.begin_transaction();
+        tx.put("key".into(), b"after".to_vec()).await.unwrap();
+        tx.abort();
+
+        assert_eq!(client.get_lww("key".into()).await.unwrap(), b"before");
+        assert_eq!(kvs.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn dropping_uncommitted_writes_warns() {
+        let mut client = Client::new(test_config()).unwrap();
+        let capture = Arc::new(SpanCapture::default());
+        let _guard = tracing::dispatcher::set_default(&tracing::Dispatch::from(capture.clone()));
+        let warnings = || {
+            capture
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(level, _)| *level == tracing::Level::WARN)
+                .count()
+        };
+
+        let mut tx = client.begin_transaction();
+        tx.put("key".into(), b"value".to_vec()).await.unwrap();
+        tx.abort();
+        assert_eq!(warnings(), 0);
+
+        let mut tx = client.begin_transaction();
+        tx.put("key".into(), b"value".to_vec()).await.unwrap();
+        drop(tx);
+        assert_eq!(warnings(), 1);
+        let events = capture.events.lock().unwrap();
+        assert!(
+            events
+                .iter()
+                .any(|(_, message)| message.contains("1 buffered writes")),
+            "{:?}",
+            events
+        );
+    }
 }
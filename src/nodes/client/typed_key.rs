@@ -0,0 +1,101 @@
+//! Provides [`TypedKey`] for associating keys with their value type.
+
+use std::{fmt, marker::PhantomData};
+
+use anna_api::ClientKey;
+
+use super::{
+    redis_like::{FromAnnaValue, ToAnnaValue},
+    Client, ClientError,
+};
+
+/// A [`ClientKey`] that is associated with the type of its value at compile time.
+///
+/// Reading or writing a typed key through [`Client::get_typed`] and
+/// [`Client::put_typed`] ensures that the value type can't be mismatched at call sites.
+///
+/// ```
+/// use wasmedge_anna_client::TypedKey;
+///
+/// let visits: TypedKey<u64> = TypedKey::new("visits");
+/// assert_eq!(visits.key(), &"visits".into());
+/// ```
+pub struct TypedKey<V> {
+    key: ClientKey,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<V> TypedKey<V> {
+    /// Creates a typed key.
+    pub fn new(key: impl Into<ClientKey>) -> Self {
+        Self {
+            key: key.into(),
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns the untyped key.
+    pub fn key(&self) -> &ClientKey {
+        &self.key
+    }
+
+    /// Converts the typed key into the untyped key.
+    pub fn into_key(self) -> ClientKey {
+        self.key
+    }
+}
+
+impl<V> Clone for TypedKey<V> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<V> fmt::Debug for TypedKey<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypedKey").field(&self.key).finish()
+    }
+}
+
+impl Client {
+    /// Try to get the *last writer wins* value of the given typed key.
+    pub async fn get_typed<V: FromAnnaValue>(
+        &mut self,
+        key: TypedKey<V>,
+    ) -> Result<V, ClientError> {
+        let value = self.get_lww(key.into_key()).await?;
+        Ok(V::from_anna_value(&value)?)
+    }
+
+    /// Try to put a *last writer wins* value with the given typed key.
+    pub async fn put_typed<V: ToAnnaValue>(
+        &mut self,
+        key: TypedKey<V>,
+        value: V,
+    ) -> Result<(), ClientError> {
+        self.put_lww(key.into_key(), value.to_anna_value()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::nodes::client::tests::{cache_key_address, test_config, MockKvs};
+
+    #[tokio::test]
+    async fn typed_round_trip() {
+        let kvs = MockKvs::start(Duration::ZERO).await;
+        let mut client = Client::new(test_config()).unwrap();
+        let count: TypedKey<i64> = TypedKey::new("count");
+        let name: TypedKey<String> = TypedKey::new("name");
+        cache_key_address(&mut client, count.key(), kvs.addr);
+        cache_key_address(&mut client, name.key(), kvs.addr);
+
+        client.put_typed(count.clone(), -42).await.unwrap();
+        client.put_typed(name.clone(), "anna".into()).await.unwrap();
+        assert_eq!(client.get_typed(count).await.unwrap(), -42);
+        assert_eq!(client.get_typed(name).await.unwrap(), "anna");
+    }
+}
@@ -3,6 +3,8 @@
 pub mod client;
 pub use self::client::*;
 
+use std::sync::{Arc, Mutex};
+
 use eyre::{bail, Context};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -19,20 +21,90 @@ pub async fn send_tcp_message(
     message: &TcpMessage,
     stream_tx: &mut tcp::OwnedWriteHalf,
 ) -> eyre::Result<()> {
-    let serialized = serde_json::to_vec(&message).context("failed to serialize tcp message")?;
-    let len = (serialized.len() as u64).to_le_bytes();
-    stream_tx
-        .write_all(&len)
-        .await
-        .context("failed to send message length")?;
+    send_tcp_message_buffered(message, stream_tx, &mut Vec::new()).await
+}
+
+/// Sends the given message on the given tcp stream, using `buf` for serialization.
+///
+/// The buffer is cleared before use. Passing the same buffer to subsequent calls
+/// avoids allocating a new serialization buffer for every message.
+pub async fn send_tcp_message_buffered(
+    message: &TcpMessage,
+    stream_tx: &mut tcp::OwnedWriteHalf,
+    buf: &mut Vec<u8>,
+) -> eyre::Result<()> {
+    const LEN_SIZE: usize = std::mem::size_of::<u64>();
+
+    buf.clear();
+    buf.extend_from_slice(&[0; LEN_SIZE]);
+    serde_json::to_writer(&mut *buf, message).context("failed to serialize tcp message")?;
+    let len = ((buf.len() - LEN_SIZE) as u64).to_le_bytes();
+    buf[..LEN_SIZE].copy_from_slice(&len);
     stream_tx
-        .write_all(&serialized)
+        .write_all(buf)
         .await
         .context("failed to send message")?;
-    log::trace!("Sent tcp message: {:?}", message);
+    tracing::trace!("Sent tcp message: {:?}", message);
     Ok(())
 }
 
+/// A pool of reusable buffers for [`send_tcp_message_buffered`].
+///
+/// Cloned pools share the same buffers.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Buffers that grew beyond this capacity are not kept in the pool.
+    const MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+    /// Creates a pool that keeps at most `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Default::default(),
+            max_buffers,
+        }
+    }
+
+    /// Takes a buffer from the pool, or allocates a new one if the pool is empty.
+    pub fn take(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    /// Clears the given buffer and returns it to the pool.
+    ///
+    /// The buffer is dropped if the pool is full or the buffer is very large.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() > Self::MAX_BUFFER_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    /// Returns whether the pool has no idle buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
 /// Receives a [`TcpMessage`] from the given stream.
 ///
 /// This function requires that all messages are sent using [`send_tcp_message`],
@@ -61,7 +133,7 @@ pub async fn receive_tcp_message(
     let mut buf = vec![0; len.try_into().unwrap()];
     if let Err(err) = stream_rx.read_exact(&mut buf).await {
         if err.kind() == std::io::ErrorKind::UnexpectedEof {
-            log::warn!("receive tcp message failed: {}", err);
+            tracing::warn!("receive tcp message failed: {}", err);
             return Ok(None);
         } else {
             return Err(eyre::Error::new(err).wrap_err("failed to read message"));
@@ -75,6 +147,34 @@ pub async fn receive_tcp_message(
             )
         })
         .map(Some);
-    log::trace!("Received tcp message: {:?}", res);
+    tracing::trace!("Received tcp message: {:?}", res);
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_pool_reuses_buffers() {
+        let pool = BufferPool::new(1);
+        let mut buf = pool.take();
+        buf.extend_from_slice(b"message");
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        pool.put(Vec::with_capacity(8));
+        assert_eq!(pool.len(), 1);
+
+        let buf = pool.take();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_ptr(), ptr);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn buffer_pool_drops_large_buffers() {
+        let pool = BufferPool::new(1);
+        pool.put(Vec::with_capacity(BufferPool::MAX_BUFFER_CAPACITY + 1));
+        assert!(pool.is_empty());
+    }
+}